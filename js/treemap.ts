@@ -1,13 +1,28 @@
 type Unit = {
+    id: string;
     name: string;
     fuzzy_match_percent: number;
     color: string;
+    directory?: boolean;
+    // Only set on nodes loaded from the treemap data endpoint
+    kind?: "directory" | "unit" | "function" | "section";
+    complete?: boolean;
+    recently_changed?: boolean;
+    children?: Unit[];
     x: number;
     y: number;
     w: number;
     h: number;
 };
 
+type TreemapData = {
+    label: string | null;
+    view: string;
+    units: Unit[];
+};
+
+type ColorMode = "fuzzy" | "complete" | "recent";
+
 const unitBounds = (unit: Unit, width: number, height: number) => {
     return {
         x: unit.x * width,
@@ -17,6 +32,25 @@ const unitBounds = (unit: Unit, width: number, height: number) => {
     };
 }
 
+const COLOR_INCOMPLETE = "#2a3140";
+const COLOR_COMPLETE = "#00c800";
+const COLOR_RECENT = "#e69f00";
+const DIMMED_ALPHA = 0.25;
+// Size range accepted for the w and h query parameters
+const MIN_REQUEST_SIZE = 16;
+const MAX_REQUEST_SIZE = 2048;
+
+const unitColor = (unit: Unit, mode: ColorMode): string => {
+    switch (mode) {
+        case "complete":
+            return unit.complete ? COLOR_COMPLETE : COLOR_INCOMPLETE;
+        case "recent":
+            return unit.recently_changed ? COLOR_RECENT : COLOR_INCOMPLETE;
+        default:
+            return unit.color;
+    }
+}
+
 const BORDER_RADIUS = 5;
 const PADDING_W = 10;
 const PADDING_H = 5;
@@ -82,6 +116,32 @@ let hovered = null;
 let dirty = false;
 let isTouch = false;
 let cachedCanvas: HTMLCanvasElement = null;
+// Set when the cached canvas needs to be redrawn
+let stale = false;
+// Interactive state, once the treemap data has loaded
+let data: TreemapData | null = null;
+// Zoomed nodes, each with its own level of units loaded from the data endpoint
+let zoomStack: { unit: Unit, units: Unit[] }[] = [];
+let dataUrl: URL | null = null;
+let loading = false;
+let colorMode: ColorMode = "fuzzy";
+let searchQuery = "";
+const matchCache = new Map<Unit, boolean>();
+
+// Whether the unit or any of its descendants matches the search query
+const matchesSearch = (unit: Unit): boolean => {
+    if (!searchQuery) {
+        return true;
+    }
+    let result = matchCache.get(unit);
+    if (result === undefined) {
+        result = unit.name.toLowerCase().includes(searchQuery)
+            || unit.id.toLowerCase().includes(searchQuery)
+            || (!!unit.kind && !!unit.children && unit.children.some(matchesSearch));
+        matchCache.set(unit, result);
+    }
+    return result;
+}
 
 const setup = (ctx: CanvasRenderingContext2D, ratio: number, width: number, height: number) => {
     ctx.setTransform(ratio, 0, 0, ratio, 0, 0); // Scale to device pixel ratio
@@ -98,7 +158,38 @@ const drawUnits = (ctx: CanvasRenderingContext2D, units: Unit[], width: number,
         ctx.rect(x, y, w, h);
         ctx.fill();
         ctx.stroke();
+        if (unit.children) {
+            drawUnits(ctx, unit.children, width, height);
+        }
+    }
+}
+
+const drawNodes = (ctx: CanvasRenderingContext2D, units: Unit[], width: number, height: number) => {
+    for (const unit of units) {
+        const {x, y, w, h} = unitBounds(unit, width, height);
+        ctx.globalAlpha = matchesSearch(unit) ? 1 : DIMMED_ALPHA;
+        ctx.fillStyle = unitColor(unit, colorMode);
+        ctx.beginPath();
+        ctx.rect(x, y, w, h);
+        ctx.fill();
+        ctx.stroke();
+        // Directory previews are already laid out inside their parent
+        if (unit.children) {
+            drawNodes(ctx, unit.children, width, height);
+        }
     }
+    ctx.globalAlpha = 1;
+}
+
+// Units currently shown: the level of the zoomed node, or the initial units
+const visibleUnits = (units: Unit[]): Unit[] => {
+    if (!data) {
+        return units;
+    }
+    if (zoomStack.length) {
+        return zoomStack[zoomStack.length - 1].units;
+    }
+    return data.units;
 }
 
 const draw = (canvas: HTMLCanvasElement, units: Unit[]) => {
@@ -106,7 +197,7 @@ const draw = (canvas: HTMLCanvasElement, units: Unit[]) => {
     const ratio = window.devicePixelRatio;
     const renderWidth = width * ratio;
     const renderHeight = height * ratio;
-    if (!dirty && canvas.width === renderWidth && canvas.height === renderHeight) {
+    if (!dirty && !stale && canvas.width === renderWidth && canvas.height === renderHeight) {
         // Nothing changed
         return;
     }
@@ -117,7 +208,8 @@ const draw = (canvas: HTMLCanvasElement, units: Unit[]) => {
         canvas.height = renderHeight;
     }
     // Update cached canvas if needed
-    if (cachedCanvas.width !== renderWidth || cachedCanvas.height !== renderHeight) {
+    if (stale || cachedCanvas.width !== renderWidth || cachedCanvas.height !== renderHeight) {
+        stale = false;
         cachedCanvas.width = renderWidth;
         cachedCanvas.height = renderHeight;
         const cachedCtx = cachedCanvas.getContext("2d");
@@ -125,7 +217,11 @@ const draw = (canvas: HTMLCanvasElement, units: Unit[]) => {
             return;
         }
         setup(cachedCtx, ratio, width, height);
-        drawUnits(cachedCtx, units, width, height);
+        if (data) {
+            drawNodes(cachedCtx, visibleUnits(units), width, height);
+        } else {
+            drawUnits(cachedCtx, units, width, height);
+        }
     }
     const ctx = canvas.getContext("2d");
     if (!ctx) {
@@ -148,7 +244,7 @@ const findUnit = (canvas: HTMLCanvasElement, units: Unit[], clientX: number, cli
     const {width, height, left, top} = canvas.getBoundingClientRect();
     const mx = clientX - left;
     const my = clientY - top;
-    for (const unit of units) {
+    for (const unit of visibleUnits(units)) {
         const {x, y, w, h} = unitBounds(unit, width, height);
         if (mx >= x && mx <= x + w && my >= y && my <= y + h) {
             return unit;
@@ -157,7 +253,151 @@ const findUnit = (canvas: HTMLCanvasElement, units: Unit[], clientX: number, cli
     return null;
 }
 
-const drawTreemap = (id: string, clickable: boolean, units: Unit[]) => {
+// Link to the report page for a node, or null if it has none
+const unitUrl = (unit: Unit): string | null => {
+    const url = new URL(window.location.href);
+    switch (unit.kind) {
+        case "directory":
+            url.searchParams.set("path", unit.id);
+            break;
+        case "unit":
+            url.searchParams.set("unit", unit.id);
+            break;
+        case "function": {
+            // Functions belong to the innermost zoomed unit, if not the current page's unit
+            const parent = zoomStack.map((z) => z.unit).reverse().find((u) => u.kind === "unit");
+            if (parent) {
+                url.searchParams.set("unit", parent.id);
+            }
+            url.searchParams.set("function", unit.id);
+            break;
+        }
+        default:
+            return null;
+    }
+    return url.toString();
+}
+
+const updateBreadcrumbs = (id: string, queueDraw: () => void) => {
+    const breadcrumbs = document.getElementById(`${id}-breadcrumbs`);
+    if (!breadcrumbs || !data) {
+        return;
+    }
+    breadcrumbs.replaceChildren();
+    const items = [{name: data.label || "All", depth: 0}]
+        .concat(zoomStack.map(({unit}, i) => ({name: unit.name, depth: i + 1})));
+    for (const item of items) {
+        const li = document.createElement("li");
+        if (item.depth === zoomStack.length) {
+            li.textContent = item.name;
+        } else {
+            const a = document.createElement("a");
+            a.href = "#";
+            a.textContent = item.name;
+            a.addEventListener("click", (e) => {
+                e.preventDefault();
+                zoomStack = zoomStack.slice(0, item.depth);
+                hovered = null;
+                stale = true;
+                updateBreadcrumbs(id, queueDraw);
+                queueDraw();
+            });
+            li.appendChild(a);
+        }
+        breadcrumbs.appendChild(li);
+    }
+    const open = document.getElementById(`${id}-open`) as HTMLAnchorElement;
+    if (open) {
+        const url = zoomStack.length ? unitUrl(zoomStack[zoomStack.length - 1].unit) : null;
+        open.hidden = !url;
+        open.href = url || "#";
+    }
+}
+
+const fetchTreemapData = (url: URL): Promise<TreemapData> => {
+    return fetch(url, {headers: {"Accept": "application/json"}})
+        .then((res) => res.ok ? res.json() : Promise.reject(res.statusText));
+}
+
+// Loads the level inside a directory or unit node and zooms into it
+const zoomInto = (id: string, unit: Unit, queueDraw: () => void) => {
+    if (!dataUrl || loading) {
+        return;
+    }
+    const url = new URL(dataUrl);
+    url.searchParams.delete("function");
+    if (unit.kind === "directory") {
+        url.searchParams.delete("unit");
+        url.searchParams.set("path", unit.id);
+    } else {
+        url.searchParams.delete("path");
+        url.searchParams.set("unit", unit.id);
+    }
+    loading = true;
+    fetchTreemapData(url)
+        .then((result) => {
+            if (!result.units.length) {
+                const link = unitUrl(unit);
+                if (link) {
+                    window.location.href = link;
+                }
+                return;
+            }
+            zoomStack.push({unit, units: result.units});
+            hovered = null;
+            stale = true;
+            matchCache.clear();
+            updateBreadcrumbs(id, queueDraw);
+            queueDraw();
+        })
+        .catch((e) => console.warn("Failed to load treemap data", e))
+        .finally(() => loading = false);
+}
+
+const loadTreemapData = (id: string, canvas: HTMLCanvasElement, baseUrl: string, queueDraw: () => void) => {
+    const url = new URL(baseUrl, window.location.href);
+    const {width, height} = canvas.getBoundingClientRect();
+    if (width > 0 && height > 0) {
+        // Only the aspect ratio matters, so fit it within the server's size range
+        const scale = Math.min(MAX_REQUEST_SIZE / Math.max(width, height), 1);
+        const size = (n: number) => Math.max(Math.round(n * scale), MIN_REQUEST_SIZE);
+        url.searchParams.set("w", size(width).toString());
+        url.searchParams.set("h", size(height).toString());
+    }
+    dataUrl = url;
+    fetchTreemapData(url)
+        .then((result) => {
+            data = result;
+            hovered = null;
+            stale = true;
+            const controls = document.getElementById(`${id}-controls`);
+            if (controls) {
+                controls.hidden = false;
+            }
+            const search = document.getElementById(`${id}-search`) as HTMLInputElement;
+            if (search) {
+                search.addEventListener("input", () => {
+                    searchQuery = search.value.trim().toLowerCase();
+                    matchCache.clear();
+                    stale = true;
+                    queueDraw();
+                });
+            }
+            const color = document.getElementById(`${id}-color`) as HTMLSelectElement;
+            if (color) {
+                color.addEventListener("change", () => {
+                    colorMode = color.value as ColorMode;
+                    stale = true;
+                    queueDraw();
+                });
+            }
+            updateBreadcrumbs(id, queueDraw);
+            queueDraw();
+        })
+        .catch((e) => console.warn("Failed to load treemap data", e));
+}
+
+const drawTreemap = (id: string, clickParam: string | null, units: Unit[], dataUrl?: string) => {
     const canvas = document.getElementById(id) as HTMLCanvasElement;
     if (!canvas || !canvas.getContext) {
         return;
@@ -165,6 +405,7 @@ const drawTreemap = (id: string, clickable: boolean, units: Unit[]) => {
     if (!cachedCanvas) {
         cachedCanvas = document.createElement("canvas");
     }
+    const clickable = clickParam !== null || !!dataUrl;
     const queueDraw = () => requestAnimationFrame(() => draw(canvas, units));
     const resizeObserver = new ResizeObserver(queueDraw);
     resizeObserver.observe(canvas);
@@ -203,19 +444,40 @@ const drawTreemap = (id: string, clickable: boolean, units: Unit[]) => {
     canvas.addEventListener("touchend", handleLeave);
     canvas.addEventListener("click", ({clientX, clientY}) => {
         const unit = findUnit(canvas, units, clientX, clientY);
-        if (!unit || !unit.name || !clickable) {
+        if (data) {
+            if (!unit) {
+                return;
+            }
+            if (unit.kind === "directory" || unit.kind === "unit") {
+                zoomInto(id, unit, queueDraw);
+            } else {
+                const url = unitUrl(unit);
+                if (url) {
+                    window.location.href = url;
+                }
+            }
+            return;
+        }
+        if (!unit || !unit.id || !clickParam) {
             return;
         }
         const url = new URL(window.location.href);
-        url.searchParams.set("unit", unit.name);
+        if (unit.directory) {
+            url.searchParams.set("path", unit.id);
+        } else {
+            url.searchParams.set(clickParam, unit.id);
+        }
         window.location.href = url.toString();
     });
     draw(canvas, units);
+    if (dataUrl) {
+        loadTreemapData(id, canvas, dataUrl, queueDraw);
+    }
 };
 
 // noinspection JSUnusedGlobalSymbols
 interface Window {
-    drawTreemap: (id: string, clickable: boolean, units: Unit[]) => void;
+    drawTreemap: (id: string, clickParam: string | null, units: Unit[], dataUrl?: string) => void;
 }
 
 window.drawTreemap = drawTreemap;
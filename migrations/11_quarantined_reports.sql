@@ -0,0 +1,23 @@
+CREATE TABLE quarantined_reports
+(
+    id             INTEGER PRIMARY KEY,
+    report_id      INTEGER   NOT NULL, -- Original report ID
+    project_id     INTEGER   NOT NULL, -- GitHub repository ID
+    version        TEXT      NOT NULL, -- Game ID
+    git_commit     TEXT      NOT NULL, -- Git commit SHA
+    timestamp      DATETIME  NOT NULL, -- Git commit timestamp
+    data           BLOB      NOT NULL, -- Serialized report data
+    reason         TEXT      NOT NULL, -- Why the report was quarantined
+    quarantined_at TIMESTAMP NOT NULL
+);
+
+CREATE TABLE quarantined_report_units
+(
+    quarantined_report_id INTEGER NOT NULL,
+    report_unit_id        BLOB    NOT NULL, -- Not a foreign key: the unit may be deleted if corrupt
+    unit_index            INTEGER NOT NULL,
+    PRIMARY KEY (quarantined_report_id, report_unit_id, unit_index),
+    FOREIGN KEY (quarantined_report_id) REFERENCES quarantined_reports (id)
+);
+
+CREATE INDEX quarantined_report_units_report_unit_id_index ON quarantined_report_units (report_unit_id);
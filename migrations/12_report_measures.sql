@@ -0,0 +1,23 @@
+CREATE TABLE report_measures
+(
+    report_id                 INTEGER NOT NULL,
+    category_id               TEXT    NOT NULL, -- Progress category ID, or empty for the overall measures
+    fuzzy_match_percent       REAL    NOT NULL,
+    total_code                INTEGER NOT NULL,
+    matched_code              INTEGER NOT NULL,
+    matched_code_percent      REAL    NOT NULL,
+    total_data                INTEGER NOT NULL,
+    matched_data              INTEGER NOT NULL,
+    matched_data_percent      REAL    NOT NULL,
+    total_functions           INTEGER NOT NULL,
+    matched_functions         INTEGER NOT NULL,
+    matched_functions_percent REAL    NOT NULL,
+    complete_code             INTEGER NOT NULL,
+    complete_code_percent     REAL    NOT NULL,
+    complete_data             INTEGER NOT NULL,
+    complete_data_percent     REAL    NOT NULL,
+    total_units               INTEGER NOT NULL,
+    complete_units            INTEGER NOT NULL,
+    PRIMARY KEY (report_id, category_id),
+    FOREIGN KEY (report_id) REFERENCES reports (id)
+);
@@ -0,0 +1,19 @@
+CREATE VIRTUAL TABLE search_index USING fts5
+(
+    name,                           -- Unit or function name
+    demangled_name,                 -- Demangled function name
+    kind UNINDEXED,                 -- "unit" or "function"
+    unit_name UNINDEXED,            -- Containing unit name
+    report_id UNINDEXED,            -- Report the entry belongs to
+    fuzzy_match_percent UNINDEXED,
+    tokenize = 'trigram'
+);
+
+CREATE TABLE search_reports
+(
+    project_id INTEGER NOT NULL,
+    version    TEXT    NOT NULL COLLATE NOCASE,
+    report_id  INTEGER NOT NULL, -- Latest report for the project version, which is indexed
+    PRIMARY KEY (project_id, version),
+    FOREIGN KEY (report_id) REFERENCES reports (id)
+);
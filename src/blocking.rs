@@ -0,0 +1,74 @@
+use std::{fmt, sync::Arc, time::Duration};
+
+use anyhow::{Context, Result};
+use tokio::sync::Semaphore;
+
+use crate::config::LimitsConfig;
+
+/// CPU-heavy work that runs on the blocking thread pool, each kind with its own concurrency
+/// limit.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Task {
+    /// Decompressing and decoding reports
+    Decode,
+    /// Rasterizing a single image
+    Render,
+    /// Rasterizing and encoding an animation
+    Animation,
+}
+
+/// Returned when no slot for a task frees up in time. Handlers respond with
+/// 503 Service Unavailable and a `Retry-After` header.
+#[derive(Debug)]
+pub struct Busy(pub Task);
+
+impl fmt::Display for Busy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Too many concurrent {:?} tasks", self.0)
+    }
+}
+
+impl std::error::Error for Busy {}
+
+#[derive(Clone)]
+pub struct BlockingPool {
+    decode: Arc<Semaphore>,
+    render: Arc<Semaphore>,
+    animation: Arc<Semaphore>,
+    queue_timeout: Duration,
+}
+
+impl BlockingPool {
+    pub fn new(config: &LimitsConfig) -> Self {
+        Self {
+            decode: Arc::new(Semaphore::new(config.max_decodes.max(1))),
+            render: Arc::new(Semaphore::new(config.max_renders.max(1))),
+            animation: Arc::new(Semaphore::new(config.max_animations.max(1))),
+            queue_timeout: Duration::from_millis(config.queue_timeout),
+        }
+    }
+
+    /// Runs `f` on the blocking thread pool once a slot for the task is free. Fails with
+    /// [`Busy`] if none frees up within the queue timeout.
+    pub async fn run<F, R>(&self, task: Task, f: F) -> Result<R>
+    where
+        F: FnOnce() -> Result<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        let semaphore = match task {
+            Task::Decode => &self.decode,
+            Task::Render => &self.render,
+            Task::Animation => &self.animation,
+        };
+        let permit = tokio::time::timeout(self.queue_timeout, semaphore.clone().acquire_owned())
+            .await
+            .map_err(|_| Busy(task))?
+            .context("Blocking pool closed")?;
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            f()
+        })
+        .await
+        .context("Blocking task panicked")?
+    }
+}
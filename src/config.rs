@@ -4,6 +4,8 @@ use serde::{Deserialize, Serialize};
 pub struct Config {
     pub server: ServerConfig,
     pub app: AppConfig,
+    #[serde(default)]
+    pub limits: LimitsConfig,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -15,4 +17,39 @@ pub struct ServerConfig {
 pub struct AppConfig {
     pub db_url: String,
     pub github_token: String,
+    /// Maximum total encoded size of cached reports, in bytes
+    #[serde(default = "default_report_cache_size")]
+    pub report_cache_size: u64,
+}
+
+fn default_report_cache_size() -> u64 { 256 * 1024 * 1024 }
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LimitsConfig {
+    /// Timeout for most requests, in seconds
+    pub request_timeout: u64,
+    /// Timeout for report requests, which may render images and animations, in seconds
+    pub render_timeout: u64,
+    /// Maximum number of reports decoded at once
+    pub max_decodes: usize,
+    /// Maximum number of images rendered at once
+    pub max_renders: usize,
+    /// Maximum number of animations rendered at once
+    pub max_animations: usize,
+    /// How long a task waits for a free slot before the request fails with 503, in milliseconds
+    pub queue_timeout: u64,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            request_timeout: 10,
+            render_timeout: 30,
+            max_decodes: 8,
+            max_renders: 4,
+            max_animations: 1,
+            queue_timeout: 2000,
+        }
+    }
 }
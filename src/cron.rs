@@ -1,7 +1,7 @@
 use anyhow::{Context, Result};
 use tokio_cron_scheduler::{Job, JobScheduler};
 
-use crate::{github, AppState};
+use crate::{github, models::ProjectSummary, AppState};
 
 pub type Scheduler = JobScheduler;
 
@@ -20,7 +20,7 @@ pub async fn create(state: AppState) -> Result<Scheduler> {
 }
 
 pub async fn refresh_projects(state: &mut AppState) -> Result<()> {
-    for project_info in state.db.get_projects().await? {
+    for ProjectSummary { info: project_info, .. } in state.db.get_projects().await? {
         github::run(state, &project_info.project.owner, &project_info.project.repo, 0)
             .await
             .with_context(|| {
@@ -0,0 +1,104 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use anyhow::Result;
+use objdiff_core::bindings::report::{Measures, ReportUnit};
+
+use super::{load_report_units_by_id, measures_from_row, Database};
+use crate::models::VersionUnits;
+
+impl Database {
+    /// Lists the report of each version of a project at a commit, with its overall or category
+    /// measures and the IDs of its units.
+    pub async fn get_commit_versions(
+        &self,
+        project_id: u64,
+        commit: &str,
+        category_id: Option<&str>,
+    ) -> Result<Vec<VersionUnits>> {
+        let project_id = project_id as i64;
+        let category_id = category_id.unwrap_or_default();
+        let mut conn = self.pool.acquire().await?;
+        let reports = sqlx::query!(
+            r#"
+            SELECT id AS "id!", version
+            FROM reports
+            WHERE project_id = ? AND git_commit = ? COLLATE NOCASE
+            "#,
+            project_id,
+            commit,
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+        let mut measures = sqlx::query!(
+            r#"
+            SELECT
+                rm.report_id,
+                rm.fuzzy_match_percent,
+                rm.total_code,
+                rm.matched_code,
+                rm.matched_code_percent,
+                rm.total_data,
+                rm.matched_data,
+                rm.matched_data_percent,
+                rm.total_functions,
+                rm.matched_functions,
+                rm.matched_functions_percent,
+                rm.complete_code,
+                rm.complete_code_percent,
+                rm.complete_data,
+                rm.complete_data_percent,
+                rm.total_units,
+                rm.complete_units
+            FROM reports JOIN report_measures rm ON rm.report_id = reports.id
+            WHERE reports.project_id = ? AND reports.git_commit = ? COLLATE NOCASE
+                  AND rm.category_id = ?
+            "#,
+            project_id,
+            commit,
+            category_id,
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|row| (row.report_id, measures_from_row!(row)))
+        .collect::<HashMap<i64, Measures>>();
+        let mut units = HashMap::<i64, Vec<(String, Vec<u8>)>>::new();
+        for row in sqlx::query!(
+            r#"
+            SELECT rru.report_id, ru.id AS "id!", ru.name
+            FROM reports
+                JOIN report_report_units rru ON rru.report_id = reports.id
+                JOIN report_units ru ON rru.report_unit_id = ru.id
+            WHERE reports.project_id = ? AND reports.git_commit = ? COLLATE NOCASE
+            ORDER BY rru.unit_index
+            "#,
+            project_id,
+            commit,
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        {
+            units.entry(row.report_id).or_default().push((row.name.unwrap_or_default(), row.id));
+        }
+        Ok(reports
+            .into_iter()
+            .map(|row| VersionUnits {
+                version: row.version,
+                measures: measures.remove(&row.id),
+                units: units.remove(&row.id).unwrap_or_default(),
+            })
+            .collect())
+    }
+
+    /// Loads report units by ID, each once.
+    pub async fn get_report_units_by_id(
+        &self,
+        ids: HashSet<Vec<u8>>,
+    ) -> Result<HashMap<Vec<u8>, Arc<ReportUnit>>> {
+        let mut conn = self.pool.acquire().await?;
+        load_report_units_by_id(&mut conn, &self.blocking, ids).await
+    }
+}
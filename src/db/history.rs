@@ -0,0 +1,168 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+
+use super::{load_report_units_by_id, Database};
+use crate::models::{Commit, UnitHistoryEntry};
+
+impl Database {
+    /// Loads a unit at each commit of a project version where its data changed, oldest first,
+    /// keeping only the `limit` most recent changes. With `before`, only commits older
+    /// than that one are included, and none if it's not a commit of the version.
+    /// Units are content-addressed, so commits where the unit's key is unchanged are skipped
+    /// without loading the unit, and each distinct unit is loaded once. Commits where the unit
+    /// was removed have no unit.
+    pub async fn get_unit_history(
+        &self,
+        project_id: u64,
+        version: &str,
+        unit_name: &str,
+        before: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<UnitHistoryEntry>> {
+        let project_id = project_id as i64;
+        let mut conn = self.pool.acquire().await?;
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                git_commit,
+                timestamp,
+                (
+                    SELECT ru.id
+                    FROM report_units ru JOIN report_report_units rru ON rru.report_unit_id = ru.id
+                    WHERE ru.name = ? AND rru.report_id = reports.id
+                ) AS "id?: Vec<u8>"
+            FROM reports
+            WHERE project_id = ? AND version = ? COLLATE NOCASE
+                AND (? IS NULL OR timestamp < (
+                    SELECT timestamp
+                    FROM reports
+                    WHERE project_id = ? AND version = ? COLLATE NOCASE AND git_commit = ?
+                ))
+            ORDER BY timestamp
+            "#,
+            unit_name,
+            project_id,
+            version,
+            before,
+            project_id,
+            version,
+            before,
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+        // Skip commits where the unit is unchanged
+        let mut changes = Vec::new();
+        let mut last_id = None;
+        for row in rows {
+            if row.id == last_id {
+                continue;
+            }
+            last_id = row.id.clone();
+            changes.push(row);
+        }
+        changes.drain(..changes.len().saturating_sub(limit));
+        // Fetch each distinct unit once
+        let ids = changes.iter().filter_map(|row| row.id.clone()).collect::<HashSet<_>>();
+        let units = load_report_units_by_id(&mut conn, &self.blocking, ids).await?;
+        Ok(changes
+            .into_iter()
+            .map(|row| UnitHistoryEntry {
+                commit: Commit { sha: row.git_commit, timestamp: row.timestamp.and_utc() },
+                unit: row.id.and_then(|id| units.get(&id).cloned()),
+            })
+            .collect())
+    }
+
+    /// Lists the commits with a report for a project version, oldest first.
+    pub async fn get_version_commits(&self, project_id: u64, version: &str) -> Result<Vec<Commit>> {
+        let project_id = project_id as i64;
+        let mut conn = self.pool.acquire().await?;
+        let rows = sqlx::query!(
+            r#"
+            SELECT git_commit, timestamp
+            FROM reports
+            WHERE project_id = ? AND version = ? COLLATE NOCASE
+            ORDER BY timestamp
+            "#,
+            project_id,
+            version,
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| Commit { sha: row.git_commit, timestamp: row.timestamp.and_utc() })
+            .collect())
+    }
+
+    /// Returns the commits of a project version whose report includes the named unit, oldest
+    /// first.
+    pub async fn get_unit_commits(
+        &self,
+        project_id: u64,
+        version: &str,
+        unit_name: &str,
+    ) -> Result<Vec<Commit>> {
+        let project_id = project_id as i64;
+        let mut conn = self.pool.acquire().await?;
+        let rows = sqlx::query!(
+            r#"
+            SELECT git_commit, timestamp
+            FROM reports
+            WHERE project_id = ? AND version = ? COLLATE NOCASE AND EXISTS (
+                SELECT 1
+                FROM report_report_units rru JOIN report_units ru ON rru.report_unit_id = ru.id
+                WHERE rru.report_id = reports.id AND ru.name = ?
+            )
+            ORDER BY timestamp
+            "#,
+            project_id,
+            version,
+            unit_name,
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| Commit { sha: row.git_commit, timestamp: row.timestamp.and_utc() })
+            .collect())
+    }
+
+    /// Returns the names of units whose data changed within the `count` most recent reports
+    /// of a project version, up to and including `timestamp`.
+    pub async fn get_recently_changed_units(
+        &self,
+        project_id: u64,
+        version: &str,
+        timestamp: DateTime<Utc>,
+        count: u32,
+    ) -> Result<HashSet<String>> {
+        let project_id = project_id as i64;
+        let mut conn = self.pool.acquire().await?;
+        let rows = sqlx::query!(
+            r#"
+            WITH recent AS (
+                SELECT id
+                FROM reports
+                WHERE project_id = ? AND version = ? COLLATE NOCASE AND timestamp <= ?
+                ORDER BY timestamp DESC
+                LIMIT ?
+            )
+            SELECT ru.name AS "name!"
+            FROM report_report_units rru JOIN report_units ru ON ru.id = rru.report_unit_id
+            WHERE rru.report_id IN recent
+            GROUP BY ru.name
+            HAVING COUNT(DISTINCT rru.report_unit_id) > 1
+            "#,
+            project_id,
+            version,
+            timestamp,
+            count,
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(rows.into_iter().map(|row| row.name).collect())
+    }
+}
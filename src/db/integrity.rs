@@ -0,0 +1,325 @@
+use std::{collections::HashSet, fmt};
+
+use anyhow::{Context, Result};
+use objdiff_core::bindings::report::Report;
+use prost::Message;
+
+use super::{decode_report_unit, decompress, search::delete_search_entries, Database};
+
+/// Number of report unit rows to load per query while scanning.
+const UNIT_BATCH_SIZE: i64 = 500;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ReportProblem {
+    /// The report's project no longer exists.
+    MissingProject,
+    /// The report data failed to decompress or decode.
+    Corrupt(String),
+    /// The report's unit indices are not contiguous.
+    UnitIndexMismatch { index: usize, expected: usize },
+    /// The report references a unit that does not exist.
+    MissingUnit { index: usize },
+    /// The report references a unit that is corrupt.
+    CorruptUnit { index: usize },
+}
+
+impl fmt::Display for ReportProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingProject => write!(f, "Project does not exist"),
+            Self::Corrupt(e) => write!(f, "Report data is corrupt: {}", e),
+            Self::UnitIndexMismatch { index, expected } => {
+                write!(f, "Report unit index mismatch: {} but expected {}", index, expected)
+            }
+            Self::MissingUnit { index } => write!(f, "Report unit {} does not exist", index),
+            Self::CorruptUnit { index } => write!(f, "Report unit {} is corrupt", index),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReportIssue {
+    pub report_id: i64,
+    pub owner: Option<String>,
+    pub repo: Option<String>,
+    pub version: String,
+    pub commit: String,
+    pub problem: ReportProblem,
+}
+
+#[derive(Debug, Clone)]
+pub struct UnitIssue {
+    pub id: Vec<u8>,
+    pub name: Option<String>,
+    pub reason: String,
+}
+
+#[derive(Debug, Default)]
+pub struct IntegrityReport {
+    pub reports_checked: usize,
+    pub units_checked: usize,
+    pub report_issues: Vec<ReportIssue>,
+    pub unit_issues: Vec<UnitIssue>,
+    /// Report units not referenced by any report.
+    pub orphaned_units: u64,
+    /// Report unit links referencing a report that does not exist.
+    pub orphaned_links: u64,
+}
+
+impl IntegrityReport {
+    pub fn is_clean(&self) -> bool {
+        self.report_issues.is_empty()
+            && self.unit_issues.is_empty()
+            && self.orphaned_units == 0
+            && self.orphaned_links == 0
+    }
+}
+
+impl Database {
+    /// Scans all reports and report units, verifying that every report decodes, that its
+    /// unit indices are contiguous, and that every unit exists, decodes and matches its hash.
+    pub async fn check_integrity(&self) -> Result<IntegrityReport> {
+        let mut result = IntegrityReport::default();
+        let corrupt_units = self.check_report_units(&mut result).await?;
+        let mut conn = self.pool.acquire().await?;
+        let reports = sqlx::query!(
+            r#"
+            SELECT
+                reports.id AS "report_id!",
+                version,
+                git_commit,
+                data,
+                projects.owner AS "owner?",
+                projects.repo AS "repo?"
+            FROM reports LEFT JOIN projects ON reports.project_id = projects.id
+            ORDER BY reports.id
+            "#,
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+        for row in reports {
+            result.reports_checked += 1;
+            let links = sqlx::query!(
+                r#"
+                SELECT rru.unit_index, rru.report_unit_id, ru.id IS NOT NULL AS "present!: bool"
+                FROM report_report_units rru LEFT JOIN report_units ru ON rru.report_unit_id = ru.id
+                WHERE rru.report_id = ?
+                ORDER BY rru.unit_index
+                "#,
+                row.report_id
+            )
+            .fetch_all(&mut *conn)
+            .await?;
+            let problem = if row.owner.is_none() {
+                Some(ReportProblem::MissingProject)
+            } else if let Err(e) = decompress(&row.data)
+                .context("Failed to decompress report data")
+                .and_then(|data| Report::decode(data.as_ref()).context("Failed to decode report"))
+            {
+                Some(ReportProblem::Corrupt(format!("{:#}", e)))
+            } else {
+                links.iter().enumerate().find_map(|(expected, link)| {
+                    let index = link.unit_index as usize;
+                    if index != expected {
+                        Some(ReportProblem::UnitIndexMismatch { index, expected })
+                    } else if !link.present {
+                        Some(ReportProblem::MissingUnit { index })
+                    } else if corrupt_units.contains(&link.report_unit_id) {
+                        Some(ReportProblem::CorruptUnit { index })
+                    } else {
+                        None
+                    }
+                })
+            };
+            if let Some(problem) = problem {
+                result.report_issues.push(ReportIssue {
+                    report_id: row.report_id,
+                    owner: row.owner,
+                    repo: row.repo,
+                    version: row.version,
+                    commit: row.git_commit,
+                    problem,
+                });
+            }
+        }
+        result.orphaned_units = sqlx::query!(
+            r#"
+            SELECT COUNT(*) AS "count!: i64"
+            FROM report_units ru
+            WHERE NOT EXISTS (
+                SELECT 1 FROM report_report_units rru WHERE rru.report_unit_id = ru.id
+            ) AND NOT EXISTS (
+                SELECT 1 FROM quarantined_report_units qru WHERE qru.report_unit_id = ru.id
+            )
+            "#,
+        )
+        .fetch_one(&mut *conn)
+        .await?
+        .count as u64;
+        result.orphaned_links = sqlx::query!(
+            r#"
+            SELECT COUNT(*) AS "count!: i64"
+            FROM report_report_units rru
+            WHERE NOT EXISTS (SELECT 1 FROM reports WHERE reports.id = rru.report_id)
+            "#,
+        )
+        .fetch_one(&mut *conn)
+        .await?
+        .count as u64;
+        Ok(result)
+    }
+
+    /// Verifies every report unit, returning the IDs of those that are corrupt.
+    async fn check_report_units(&self, result: &mut IntegrityReport) -> Result<HashSet<Vec<u8>>> {
+        let mut conn = self.pool.acquire().await?;
+        let mut corrupt = HashSet::new();
+        let mut last_id = Vec::<u8>::new();
+        loop {
+            let rows = sqlx::query!(
+                r#"
+                SELECT id AS "id!", data, name
+                FROM report_units
+                WHERE id > ?
+                ORDER BY id
+                LIMIT ?
+                "#,
+                last_id,
+                UNIT_BATCH_SIZE,
+            )
+            .fetch_all(&mut *conn)
+            .await?;
+            let Some(last) = rows.last() else {
+                break;
+            };
+            last_id = last.id.clone();
+            for row in rows {
+                result.units_checked += 1;
+                if let Err(e) = decode_report_unit(&row.id, &row.data) {
+                    corrupt.insert(row.id.clone());
+                    result.unit_issues.push(UnitIssue {
+                        id: row.id,
+                        name: row.name,
+                        reason: format!("{:#}", e),
+                    });
+                }
+            }
+        }
+        Ok(corrupt)
+    }
+
+    /// Moves a report and its unit links into the quarantine tables, removing it from
+    /// `reports` so that it's no longer served.
+    pub async fn quarantine_report(&self, report_id: i64, reason: &str) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let Some(quarantined_id) = sqlx::query!(
+            r#"
+            INSERT INTO quarantined_reports
+                (report_id, project_id, version, git_commit, timestamp, data, reason, quarantined_at)
+            SELECT id, project_id, version, git_commit, timestamp, data, ?, CURRENT_TIMESTAMP
+            FROM reports
+            WHERE id = ?
+            RETURNING id
+            "#,
+            reason,
+            report_id,
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .map(|row| row.id) else {
+            return Ok(());
+        };
+        sqlx::query!(
+            r#"
+            INSERT INTO quarantined_report_units (quarantined_report_id, report_unit_id, unit_index)
+            SELECT ?, report_unit_id, unit_index
+            FROM report_report_units
+            WHERE report_id = ?
+            ON CONFLICT DO NOTHING
+            "#,
+            quarantined_id,
+            report_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM report_report_units
+            WHERE report_id = ?
+            "#,
+            report_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM report_measures
+            WHERE report_id = ?
+            "#,
+            report_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+        delete_search_entries(&mut tx, report_id).await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM reports
+            WHERE id = ?
+            "#,
+            report_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        self.report_cache.invalidate_all();
+        Ok(())
+    }
+
+    /// Deletes corrupt report units. Any reports referencing them must be quarantined first.
+    pub async fn delete_report_units(&self, ids: &[Vec<u8>]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        for id in ids {
+            sqlx::query!(
+                r#"
+                DELETE FROM report_units
+                WHERE id = ?
+                "#,
+                id,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Deletes report unit links whose report no longer exists, followed by report units
+    /// that are no longer referenced. Returns the number of (units, links) deleted.
+    pub async fn delete_orphans(&self) -> Result<(u64, u64)> {
+        let mut tx = self.pool.begin().await?;
+        let links = sqlx::query!(
+            r#"
+            DELETE FROM report_report_units
+            WHERE NOT EXISTS (SELECT 1 FROM reports WHERE reports.id = report_id)
+            "#,
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        let units = sqlx::query!(
+            r#"
+            DELETE FROM report_units
+            WHERE NOT EXISTS (
+                SELECT 1 FROM report_report_units rru WHERE rru.report_unit_id = report_units.id
+            ) AND NOT EXISTS (
+                SELECT 1 FROM quarantined_report_units qru
+                WHERE qru.report_unit_id = report_units.id
+            )
+            "#,
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        tx.commit().await?;
+        Ok((units, links))
+    }
+}
@@ -1,20 +1,57 @@
-use std::{borrow::Cow, cell::RefCell, sync::Arc};
+mod compare;
+mod history;
+mod integrity;
+mod search;
+
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
 use moka::future::Cache;
-use objdiff_core::bindings::report::{Report, ReportUnit};
+use objdiff_core::bindings::report::{Measures, Report, ReportUnit, REPORT_VERSION};
 use prost::Message;
-use sqlx::{migrate::MigrateDatabase, Pool, Sqlite, SqlitePool};
+use serde::Serialize;
+use sqlx::{migrate::MigrateDatabase, Pool, QueryBuilder, Sqlite, SqliteConnection, SqlitePool};
 
+pub use self::search::MIN_QUERY_LEN;
 use crate::{
+    blocking::{BlockingPool, Busy, Task},
     config::AppConfig,
-    models::{Commit, Project, ProjectInfo, ReportFile},
+    models::{Commit, Project, ProjectInfo, ProjectSummary, ReportFile},
 };
 
+/// Maximum number of bound parameters per query, well under SQLite's limit.
+const MAX_BIND_PARAMS: usize = 500;
+/// How long cache warming waits before retrying when the decode pool is saturated.
+const WARM_RETRY_DELAY: Duration = Duration::from_millis(500);
+
 #[derive(Clone)]
 pub struct Database {
     pool: Pool<Sqlite>,
+    blocking: BlockingPool,
     report_cache: Cache<ReportKey, Arc<Report>>,
+    report_cache_hits: Arc<AtomicU64>,
+    report_cache_misses: Arc<AtomicU64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheStats {
+    pub entries: u64,
+    /// Total encoded size of cached reports, in bytes
+    pub size: u64,
+    /// Maximum total encoded size, in bytes
+    pub capacity: u64,
+    pub hits: u64,
+    pub misses: u64,
 }
 
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
@@ -28,8 +65,47 @@ struct ReportKey {
 // BLAKE3 hash of the unit data
 type UnitKey = [u8; 32];
 
+/// Which units to load along with a report.
+#[derive(Debug, Copy, Clone)]
+enum LoadUnits<'a> {
+    /// Only the report header (measures and categories).
+    None,
+    /// Only the unit with the given name.
+    Named(&'a str),
+    /// Every unit.
+    All,
+    /// Every unit, without adding the report to the cache.
+    Uncached,
+}
+
+/// Builds [`Measures`] from a query row with columns named after its fields.
+macro_rules! measures_from_row {
+    ($row:expr) => {
+        Measures {
+            fuzzy_match_percent: $row.fuzzy_match_percent as f32,
+            total_code: $row.total_code as u64,
+            matched_code: $row.matched_code as u64,
+            matched_code_percent: $row.matched_code_percent as f32,
+            total_data: $row.total_data as u64,
+            matched_data: $row.matched_data as u64,
+            matched_data_percent: $row.matched_data_percent as f32,
+            total_functions: $row.total_functions as u32,
+            matched_functions: $row.matched_functions as u32,
+            matched_functions_percent: $row.matched_functions_percent as f32,
+            complete_code: $row.complete_code as u64,
+            complete_code_percent: $row.complete_code_percent as f32,
+            complete_data: $row.complete_data as u64,
+            complete_data_percent: $row.complete_data_percent as f32,
+            total_units: $row.total_units as u32,
+            complete_units: $row.complete_units as u32,
+        }
+    };
+}
+// Allow submodules declared above the macro to import it
+use measures_from_row;
+
 impl Database {
-    pub async fn new(config: &AppConfig) -> Result<Self> {
+    pub async fn new(config: &AppConfig, blocking: BlockingPool) -> Result<Self> {
         if !Sqlite::database_exists(&config.db_url).await.unwrap_or(false) {
             tracing::info!(db_url = %config.db_url, "Creating database");
             Sqlite::create_database(&config.db_url).await.context("Failed to create database")?;
@@ -41,8 +117,18 @@ impl Database {
             .run(&pool)
             .await
             .context("Failed to run database migrations")?;
-        let report_cache = Cache::builder().max_capacity(100).build();
-        let db = Self { pool, report_cache };
+        let report_cache = Cache::builder()
+            // Weigh reports by their encoded size
+            .weigher(|_, report: &Arc<Report>| report.encoded_len().try_into().unwrap_or(u32::MAX))
+            .max_capacity(config.report_cache_size)
+            .build();
+        let db = Self {
+            pool,
+            blocking,
+            report_cache,
+            report_cache_hits: Default::default(),
+            report_cache_misses: Default::default(),
+        };
         db.fixup_report_units().await?;
         Ok(db)
     }
@@ -93,6 +179,7 @@ impl Database {
         .fetch_one(&mut *tx)
         .await?
         .id;
+        insert_report_measures(&mut tx, report_id, &file.report).await?;
         let mut keys = Vec::with_capacity(file.report.units.len());
         for unit in &file.report.units {
             let mut data = unit.encode_to_vec();
@@ -129,6 +216,8 @@ impl Database {
             .execute(&mut *tx)
             .await?;
         }
+        search::update_search_index(&mut tx, project_id, &file.version, report_id, &file.report)
+            .await?;
         tx.commit().await?;
         self.report_cache
             .insert(
@@ -150,6 +239,53 @@ impl Database {
         repo: &str,
         commit: &str,
         version: &str,
+    ) -> Result<Option<ReportFile>> {
+        self.fetch_report(owner, repo, commit, version, LoadUnits::All).await
+    }
+
+    /// Fetches a report with every unit, without adding it to the report cache. For one-off
+    /// loads of many reports, like timelapse frames.
+    pub async fn get_report_uncached(
+        &self,
+        owner: &str,
+        repo: &str,
+        commit: &str,
+        version: &str,
+    ) -> Result<Option<ReportFile>> {
+        self.fetch_report(owner, repo, commit, version, LoadUnits::Uncached).await
+    }
+
+    /// Fetches a report without any units. Only the top-level and category measures are
+    /// available.
+    pub async fn get_report_header(
+        &self,
+        owner: &str,
+        repo: &str,
+        commit: &str,
+        version: &str,
+    ) -> Result<Option<ReportFile>> {
+        self.fetch_report(owner, repo, commit, version, LoadUnits::None).await
+    }
+
+    /// Fetches a report with only the named unit, if it exists.
+    pub async fn get_report_unit(
+        &self,
+        owner: &str,
+        repo: &str,
+        commit: &str,
+        version: &str,
+        unit_name: &str,
+    ) -> Result<Option<ReportFile>> {
+        self.fetch_report(owner, repo, commit, version, LoadUnits::Named(unit_name)).await
+    }
+
+    async fn fetch_report(
+        &self,
+        owner: &str,
+        repo: &str,
+        commit: &str,
+        version: &str,
+        units: LoadUnits<'_>,
     ) -> Result<Option<ReportFile>> {
         let mut conn = self.pool.acquire().await?;
         let (report_id, project, commit, version, mut report) = match sqlx::query!(
@@ -180,8 +316,14 @@ impl Database {
         .await?
         {
             Some(row) => {
-                let data = decompress(&row.data).context("Failed to decompress report data")?;
-                let report = Report::decode(data.as_ref()).context("Failed to decode report")?;
+                let data = row.data;
+                let report = self
+                    .blocking
+                    .run(Task::Decode, move || {
+                        let data = decompress(&data).context("Failed to decompress report data")?;
+                        Report::decode(data.as_ref()).context("Failed to decode report")
+                    })
+                    .await?;
                 (
                     row.report_id,
                     Project {
@@ -207,36 +349,48 @@ impl Database {
             version: version.to_ascii_lowercase(),
         };
         if let Some(report) = self.report_cache.get(&key).await {
+            self.report_cache_hits.fetch_add(1, Ordering::Relaxed);
             return Ok(Some(ReportFile { project, commit, version, report }));
         }
-        for row in sqlx::query!(
-            r#"
-            SELECT ru.id AS "id!", ru.data, rru.unit_index
-            FROM report_report_units rru JOIN report_units ru ON rru.report_unit_id = ru.id
-            WHERE rru.report_id = ?
-            ORDER BY rru.unit_index
-            "#,
-            report_id
-        )
-        .fetch_all(&mut *conn)
-        .await?
-        {
-            let idx = row.unit_index as usize;
-            if idx != report.units.len() {
-                bail!("Report unit index mismatch: {} but expected {}", idx, report.units.len());
+        // Older reports need all of their units to migrate measures
+        let units = match units {
+            LoadUnits::None | LoadUnits::Named(_) if report.version != REPORT_VERSION => {
+                LoadUnits::All
             }
-            let key: UnitKey = row.id.as_slice().try_into()?;
-            let data = decompress(&row.data).context("Failed to decompress report unit data")?;
-            let hash: UnitKey = blake3::hash(data.as_ref()).into();
-            if hash != key {
-                bail!("Report unit data hash mismatch for unit {}", idx);
+            units => units,
+        };
+        let cache = match units {
+            LoadUnits::None => {
+                return Ok(Some(ReportFile { project, commit, version, report: Arc::new(report) }));
             }
-            let unit = ReportUnit::decode(data.as_ref()).context("Failed to decode report unit")?;
-            report.units.push(unit);
+            LoadUnits::Named(unit_name) => {
+                if let Some(unit) =
+                    load_report_unit(&mut conn, &self.blocking, report_id, unit_name).await?
+                {
+                    report.units.push(unit);
+                }
+                return Ok(Some(ReportFile { project, commit, version, report: Arc::new(report) }));
+            }
+            LoadUnits::All => true,
+            LoadUnits::Uncached => false,
+        };
+        // Only cached loads count as misses
+        if cache {
+            self.report_cache_misses.fetch_add(1, Ordering::Relaxed);
         }
-        report.migrate()?;
+        let rows = fetch_report_units(&mut conn, report_id).await?;
+        let report = self
+            .blocking
+            .run(Task::Decode, move || {
+                decode_report_units(rows, &mut report)?;
+                report.migrate()?;
+                Ok(report)
+            })
+            .await?;
         let report = Arc::new(report);
-        self.report_cache.insert(key, report.clone()).await;
+        if cache {
+            self.report_cache.insert(key, report.clone()).await;
+        }
         Ok(Some(ReportFile { project, commit, version, report }))
     }
 
@@ -394,7 +548,7 @@ impl Database {
         Ok(Some(info))
     }
 
-    pub async fn get_projects(&self) -> Result<Vec<ProjectInfo>> {
+    pub async fn get_projects(&self) -> Result<Vec<ProjectSummary>> {
         let mut conn = self.pool.acquire().await?;
         let projects = sqlx::query!(
             r#"
@@ -406,10 +560,26 @@ impl Database {
                 short_name,
                 default_version,
                 platform,
-                git_commit,
-                MAX(timestamp) AS "timestamp: chrono::NaiveDateTime",
+                git_commit AS "git_commit?",
+                MAX(timestamp) AS "timestamp?: chrono::NaiveDateTime",
                 JSON_GROUP_ARRAY(version ORDER BY version)
-                    FILTER (WHERE version IS NOT NULL) AS versions
+                    FILTER (WHERE version IS NOT NULL) AS "versions?: String",
+                COALESCE(rm.fuzzy_match_percent, 0) AS "fuzzy_match_percent!: f64",
+                COALESCE(rm.total_code, 0) AS "total_code!: i64",
+                COALESCE(rm.matched_code, 0) AS "matched_code!: i64",
+                COALESCE(rm.matched_code_percent, 0) AS "matched_code_percent!: f64",
+                COALESCE(rm.total_data, 0) AS "total_data!: i64",
+                COALESCE(rm.matched_data, 0) AS "matched_data!: i64",
+                COALESCE(rm.matched_data_percent, 0) AS "matched_data_percent!: f64",
+                COALESCE(rm.total_functions, 0) AS "total_functions!: i64",
+                COALESCE(rm.matched_functions, 0) AS "matched_functions!: i64",
+                COALESCE(rm.matched_functions_percent, 0) AS "matched_functions_percent!: f64",
+                COALESCE(rm.complete_code, 0) AS "complete_code!: i64",
+                COALESCE(rm.complete_code_percent, 0) AS "complete_code_percent!: f64",
+                COALESCE(rm.complete_data, 0) AS "complete_data!: i64",
+                COALESCE(rm.complete_data_percent, 0) AS "complete_data_percent!: f64",
+                COALESCE(rm.total_units, 0) AS "total_units!: i64",
+                COALESCE(rm.complete_units, 0) AS "complete_units!: i64"
             FROM projects LEFT JOIN reports ON (
                 reports.project_id = projects.id
                 AND reports.timestamp = (
@@ -417,6 +587,24 @@ impl Database {
                     FROM reports
                     WHERE project_id = projects.id
                 )
+            ) LEFT JOIN report_measures rm ON (
+                rm.category_id = ''
+                -- Same as ProjectInfo::default_version
+                AND rm.report_id = COALESCE(
+                    (
+                        SELECT id
+                        FROM reports r
+                        WHERE r.project_id = projects.id AND r.git_commit = reports.git_commit
+                              AND r.version = projects.default_version
+                    ),
+                    (
+                        SELECT id
+                        FROM reports r
+                        WHERE r.project_id = projects.id AND r.git_commit = reports.git_commit
+                        ORDER BY r.version
+                        LIMIT 1
+                    )
+                )
             )
             GROUP BY projects.id
             ORDER BY MAX(timestamp) DESC
@@ -425,33 +613,218 @@ impl Database {
         .fetch_all(&mut *conn)
         .await?
         .into_iter()
-        .map(|row| ProjectInfo {
-            project: Project {
-                id: row.project_id as u64,
-                owner: row.owner,
-                repo: row.repo,
-                name: row.name,
-                short_name: row.short_name,
-                default_version: row.default_version,
-                platform: row.platform,
-            },
-            commit: match (row.git_commit, row.timestamp) {
-                (Some(sha), Some(timestamp)) => {
-                    Some(Commit { sha, timestamp: timestamp.and_utc() })
-                }
-                _ => None,
+        .map(|row| ProjectSummary {
+            measures: measures_from_row!(row),
+            info: ProjectInfo {
+                project: Project {
+                    id: row.project_id as u64,
+                    owner: row.owner,
+                    repo: row.repo,
+                    name: row.name,
+                    short_name: row.short_name,
+                    default_version: row.default_version,
+                    platform: row.platform,
+                },
+                commit: match (row.git_commit, row.timestamp) {
+                    (Some(sha), Some(timestamp)) => {
+                        Some(Commit { sha, timestamp: timestamp.and_utc() })
+                    }
+                    _ => None,
+                },
+                report_versions: row
+                    .versions
+                    .and_then(|s| serde_json::from_str(&s).ok())
+                    .unwrap_or_default(),
+                prev_commit: None,
+                next_commit: None,
             },
-            report_versions: row
-                .versions
-                .and_then(|s| serde_json::from_str(&s).ok())
-                .unwrap_or_default(),
-            prev_commit: None,
-            next_commit: None,
         })
         .collect();
         Ok(projects)
     }
 
+    /// Loads the measures of each report for a project version, oldest first. If `category_id`
+    /// is given, loads the progress category's measures instead of the overall measures.
+    pub async fn get_measures_history(
+        &self,
+        project_id: u64,
+        version: &str,
+        category_id: Option<&str>,
+    ) -> Result<Vec<(Commit, Measures)>> {
+        let project_id = project_id as i64;
+        let category_id = category_id.unwrap_or_default();
+        let mut conn = self.pool.acquire().await?;
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                reports.git_commit,
+                reports.timestamp,
+                rm.fuzzy_match_percent,
+                rm.total_code,
+                rm.matched_code,
+                rm.matched_code_percent,
+                rm.total_data,
+                rm.matched_data,
+                rm.matched_data_percent,
+                rm.total_functions,
+                rm.matched_functions,
+                rm.matched_functions_percent,
+                rm.complete_code,
+                rm.complete_code_percent,
+                rm.complete_data,
+                rm.complete_data_percent,
+                rm.total_units,
+                rm.complete_units
+            FROM reports JOIN report_measures rm ON rm.report_id = reports.id
+            WHERE reports.project_id = ? AND reports.version = ? COLLATE NOCASE
+                  AND rm.category_id = ?
+            ORDER BY reports.timestamp
+            "#,
+            project_id,
+            version,
+            category_id,
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let commit = Commit { sha: row.git_commit, timestamp: row.timestamp.and_utc() };
+                (commit, measures_from_row!(row))
+            })
+            .collect())
+    }
+
+    /// Returns the overall measures of every project version as of `date`, keyed by project ID
+    /// and version: those of the latest report at or before it, or of the first report if the
+    /// history starts later.
+    pub async fn get_measures_at(
+        &self,
+        date: DateTime<Utc>,
+    ) -> Result<HashMap<(u64, String), Measures>> {
+        let mut conn = self.pool.acquire().await?;
+        let rows = sqlx::query!(
+            r#"
+            WITH ranked AS (
+                SELECT
+                    id,
+                    project_id,
+                    version,
+                    ROW_NUMBER() OVER (
+                        PARTITION BY project_id, version
+                        ORDER BY timestamp <= ?1 DESC,
+                                 CASE WHEN timestamp <= ?1 THEN timestamp END DESC,
+                                 timestamp
+                    ) AS rank
+                FROM reports
+            )
+            SELECT
+                ranked.project_id AS "project_id!",
+                ranked.version AS "version!",
+                rm.fuzzy_match_percent,
+                rm.total_code,
+                rm.matched_code,
+                rm.matched_code_percent,
+                rm.total_data,
+                rm.matched_data,
+                rm.matched_data_percent,
+                rm.total_functions,
+                rm.matched_functions,
+                rm.matched_functions_percent,
+                rm.complete_code,
+                rm.complete_code_percent,
+                rm.complete_data,
+                rm.complete_data_percent,
+                rm.total_units,
+                rm.complete_units
+            FROM ranked JOIN report_measures rm ON rm.report_id = ranked.id
+            WHERE ranked.rank = 1 AND rm.category_id = ''
+            "#,
+            date,
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| ((row.project_id as u64, row.version), measures_from_row!(row)))
+            .collect())
+    }
+
+    /// Counts the reports of every project version since `date`, keyed by project ID and
+    /// version.
+    pub async fn get_report_counts(
+        &self,
+        date: DateTime<Utc>,
+    ) -> Result<HashMap<(u64, String), usize>> {
+        let mut conn = self.pool.acquire().await?;
+        let rows = sqlx::query!(
+            r#"
+            SELECT project_id, version, COUNT(*) AS "count!: i64"
+            FROM reports
+            WHERE timestamp > ?
+            GROUP BY project_id, version
+            "#,
+            date,
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| ((row.project_id as u64, row.version), row.count as usize))
+            .collect())
+    }
+
+    /// Loads the latest report for the default version of each project into the report cache.
+    /// Reports that fail to load are logged and skipped.
+    pub async fn warm_report_cache(&self) -> Result<()> {
+        let start = std::time::Instant::now();
+        let mut count = 0;
+        for ProjectSummary { info, .. } in self.get_projects().await? {
+            let (Some(commit), Some(version)) = (&info.commit, info.default_version()) else {
+                continue;
+            };
+            let owner = &info.project.owner;
+            let repo = &info.project.repo;
+            let result = loop {
+                match self.get_report(owner, repo, &commit.sha, version).await {
+                    // Yield to requests when the decode pool is saturated
+                    Err(e) if e.is::<Busy>() => tokio::time::sleep(WARM_RETRY_DELAY).await,
+                    result => break result,
+                }
+            };
+            match result {
+                Ok(Some(_)) => count += 1,
+                Ok(None) => {}
+                Err(e) => tracing::warn!(
+                    "Failed to warm {}/{} {} {}: {:?}",
+                    owner,
+                    repo,
+                    version,
+                    commit.sha,
+                    e
+                ),
+            }
+        }
+        self.report_cache.run_pending_tasks().await;
+        tracing::info!(
+            "Warmed report cache with {} reports ({} bytes) in {}ms",
+            count,
+            self.report_cache.weighted_size(),
+            start.elapsed().as_millis()
+        );
+        Ok(())
+    }
+
+    pub fn report_cache_stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.report_cache.entry_count(),
+            size: self.report_cache.weighted_size(),
+            capacity: self.report_cache.policy().max_capacity().unwrap_or_default(),
+            hits: self.report_cache_hits.load(Ordering::Relaxed),
+            misses: self.report_cache_misses.load(Ordering::Relaxed),
+        }
+    }
+
     async fn fixup_report_units(&self) -> Result<()> {
         let mut conn = self.pool.acquire().await?;
         for row in sqlx::query!(
@@ -480,6 +853,40 @@ impl Database {
         }
         Ok(())
     }
+
+    /// Backfills measures for reports inserted before the `report_measures` table existed.
+    /// Reports that fail to load are logged and skipped; `verify` reports them in detail.
+    pub async fn fixup_report_measures(&self) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        for row in sqlx::query!(
+            r#"
+            SELECT id AS "id!", data
+            FROM reports
+            WHERE NOT EXISTS (SELECT 1 FROM report_measures WHERE report_id = reports.id)
+            "#,
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        {
+            let result: Result<()> = async {
+                let data = decompress(&row.data).context("Failed to decompress report data")?;
+                let mut report =
+                    Report::decode(data.as_ref()).context("Failed to decode report")?;
+                if report.version != REPORT_VERSION {
+                    // Older reports need their units to migrate measures
+                    let units = fetch_report_units(&mut conn, row.id).await?;
+                    decode_report_units(units, &mut report)?;
+                    report.migrate()?;
+                }
+                insert_report_measures(&mut conn, row.id, &report).await
+            }
+            .await;
+            if let Err(e) = result {
+                tracing::warn!("Skipping measures for report {}: {:?}", row.id, e);
+            }
+        }
+        Ok(())
+    }
 }
 
 thread_local! {
@@ -494,9 +901,174 @@ thread_local! {
     };
 }
 
+async fn insert_report_measures(
+    conn: &mut SqliteConnection,
+    report_id: i64,
+    report: &Report,
+) -> Result<()> {
+    // Replace every row, so categories removed from the report don't linger
+    sqlx::query!("DELETE FROM report_measures WHERE report_id = ?", report_id)
+        .execute(&mut *conn)
+        .await?;
+    let overall = report.measures.as_ref().map(|m| ("", m));
+    let categories =
+        report.categories.iter().filter_map(|c| Some((c.id.as_str(), c.measures.as_ref()?)));
+    for (category_id, measures) in overall.into_iter().chain(categories) {
+        let total_code = measures.total_code as i64;
+        let matched_code = measures.matched_code as i64;
+        let total_data = measures.total_data as i64;
+        let matched_data = measures.matched_data as i64;
+        let complete_code = measures.complete_code as i64;
+        let complete_data = measures.complete_data as i64;
+        sqlx::query!(
+            r#"
+            INSERT INTO report_measures (
+                report_id, category_id, fuzzy_match_percent, total_code, matched_code,
+                matched_code_percent, total_data, matched_data, matched_data_percent,
+                total_functions, matched_functions, matched_functions_percent, complete_code,
+                complete_code_percent, complete_data, complete_data_percent, total_units,
+                complete_units
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+            report_id,
+            category_id,
+            measures.fuzzy_match_percent,
+            total_code,
+            matched_code,
+            measures.matched_code_percent,
+            total_data,
+            matched_data,
+            measures.matched_data_percent,
+            measures.total_functions,
+            measures.matched_functions,
+            measures.matched_functions_percent,
+            complete_code,
+            measures.complete_code_percent,
+            complete_data,
+            measures.complete_data_percent,
+            measures.total_units,
+            measures.complete_units,
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Fetches the encoded units of a report in order, as (ID, data) pairs.
+async fn fetch_report_units(
+    conn: &mut SqliteConnection,
+    report_id: i64,
+) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+    let mut units = Vec::new();
+    for row in sqlx::query!(
+        r#"
+        SELECT ru.id AS "id!", ru.data, rru.unit_index
+        FROM report_report_units rru JOIN report_units ru ON rru.report_unit_id = ru.id
+        WHERE rru.report_id = ?
+        ORDER BY rru.unit_index
+        "#,
+        report_id
+    )
+    .fetch_all(&mut *conn)
+    .await?
+    {
+        let idx = row.unit_index as usize;
+        if idx != units.len() {
+            bail!("Report unit index mismatch: {} but expected {}", idx, units.len());
+        }
+        units.push((row.id, row.data));
+    }
+    Ok(units)
+}
+
+/// Decodes units fetched with [`fetch_report_units`], appending them to `report.units`.
+fn decode_report_units(units: Vec<(Vec<u8>, Vec<u8>)>, report: &mut Report) -> Result<()> {
+    for (idx, (id, data)) in units.into_iter().enumerate() {
+        let unit = decode_report_unit(&id, &data)
+            .with_context(|| format!("Failed to load report unit {}", idx))?;
+        report.units.push(unit);
+    }
+    Ok(())
+}
+
+/// Loads and verifies a single unit of a report by name.
+async fn load_report_unit(
+    conn: &mut SqliteConnection,
+    blocking: &BlockingPool,
+    report_id: i64,
+    unit_name: &str,
+) -> Result<Option<ReportUnit>> {
+    let Some(row) = sqlx::query!(
+        r#"
+        SELECT ru.id AS "id!", ru.data, rru.unit_index
+        FROM report_report_units rru JOIN report_units ru ON rru.report_unit_id = ru.id
+        WHERE rru.report_id = ? AND ru.name = ?
+        "#,
+        report_id,
+        unit_name
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    else {
+        return Ok(None);
+    };
+    let unit = blocking
+        .run(Task::Decode, move || {
+            decode_report_unit(&row.id, &row.data)
+                .with_context(|| format!("Failed to load report unit {}", row.unit_index))
+        })
+        .await?;
+    Ok(Some(unit))
+}
+
+/// Loads and verifies report units by ID, fetching each batch of IDs in a single query.
+async fn load_report_units_by_id(
+    conn: &mut SqliteConnection,
+    blocking: &BlockingPool,
+    ids: impl IntoIterator<Item = Vec<u8>>,
+) -> Result<HashMap<Vec<u8>, Arc<ReportUnit>>> {
+    let ids = ids.into_iter().collect::<Vec<_>>();
+    let mut blobs = Vec::with_capacity(ids.len());
+    for chunk in ids.chunks(MAX_BIND_PARAMS) {
+        let mut query =
+            QueryBuilder::<Sqlite>::new("SELECT id, data FROM report_units WHERE id IN (");
+        let mut separated = query.separated(", ");
+        for id in chunk {
+            separated.push_bind(id.as_slice());
+        }
+        separated.push_unseparated(")");
+        blobs.extend(query.build_query_as::<(Vec<u8>, Vec<u8>)>().fetch_all(&mut *conn).await?);
+    }
+    blocking
+        .run(Task::Decode, move || {
+            blobs
+                .into_iter()
+                .map(|(id, data)| {
+                    let unit =
+                        decode_report_unit(&id, &data).context("Failed to load report unit")?;
+                    Ok((id, Arc::new(unit)))
+                })
+                .collect()
+        })
+        .await
+}
+
+/// Decompresses and decodes a report unit, verifying the data against its BLAKE3 key.
+fn decode_report_unit(id: &[u8], data: &[u8]) -> Result<ReportUnit> {
+    let key: UnitKey = id.try_into().context("Invalid report unit ID")?;
+    let data = decompress(data).context("Failed to decompress report unit data")?;
+    let hash: UnitKey = blake3::hash(data.as_ref()).into();
+    if hash != key {
+        bail!("Report unit data hash mismatch");
+    }
+    ReportUnit::decode(data.as_ref()).context("Failed to decode report unit")
+}
+
 fn compress(data: &[u8]) -> Vec<u8> { COMPRESSOR.with_borrow_mut(|z| z.compress(data).unwrap()) }
 
-fn decompress(data: &[u8]) -> Result<Cow<[u8]>> {
+fn decompress(data: &[u8]) -> Result<Cow<'_, [u8]>> {
     match zstd::zstd_safe::get_frame_content_size(data) {
         Ok(Some(size)) => {
             Ok(Cow::Owned(DECOMPRESSOR.with_borrow_mut(|z| z.decompress(data, size as usize))?))
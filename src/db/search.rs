@@ -0,0 +1,211 @@
+use anyhow::{Context, Result};
+use objdiff_core::bindings::report::Report;
+use prost::Message;
+use sqlx::SqliteConnection;
+
+use super::{decode_report_units, decompress, fetch_report_units, Database};
+use crate::models::SearchResult;
+
+/// Minimum query length supported by the trigram tokenizer.
+pub const MIN_QUERY_LEN: usize = 3;
+
+impl Database {
+    /// Searches unit and function names in the latest report of each project version.
+    pub async fn search(&self, query: &str, limit: u32) -> Result<Vec<SearchResult>> {
+        if query.chars().count() < MIN_QUERY_LEN {
+            return Ok(vec![]);
+        }
+        // Match the query as a single phrase
+        let query = format!("\"{}\"", query.replace('"', "\"\""));
+        let mut conn = self.pool.acquire().await?;
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                si.name AS "name!: String",
+                si.demangled_name AS "demangled_name?: String",
+                si.kind AS "kind!: String",
+                si.unit_name AS "unit_name!: String",
+                si.fuzzy_match_percent AS "fuzzy_match_percent?: f64",
+                projects.owner,
+                projects.repo,
+                projects.name AS "project_name?",
+                reports.version,
+                reports.git_commit
+            FROM search_index si
+                JOIN reports ON reports.id = si.report_id
+                JOIN projects ON projects.id = reports.project_id
+            WHERE search_index MATCH ?
+            ORDER BY rank
+            LIMIT ?
+            "#,
+            query,
+            limit,
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| SearchResult {
+                owner: row.owner,
+                repo: row.repo,
+                project_name: row.project_name,
+                version: row.version,
+                commit: row.git_commit,
+                kind: row.kind,
+                unit_name: row.unit_name,
+                name: row.name,
+                demangled_name: row.demangled_name,
+                fuzzy_match_percent: row.fuzzy_match_percent.map(|p| p as f32),
+            })
+            .collect())
+    }
+
+    /// Indexes the latest report of any project version that isn't already indexed. Reports
+    /// that fail to load are logged and skipped.
+    pub async fn fixup_search_index(&self) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        for row in sqlx::query!(
+            r#"
+            SELECT id AS "id!", project_id, version, data
+            FROM reports
+            WHERE id = (
+                SELECT r2.id FROM reports r2
+                WHERE r2.project_id = reports.project_id AND r2.version = reports.version
+                ORDER BY r2.timestamp DESC
+                LIMIT 1
+            ) AND NOT EXISTS (SELECT 1 FROM search_reports WHERE report_id = reports.id)
+            "#,
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        {
+            let result: Result<()> = async {
+                let data = decompress(&row.data).context("Failed to decompress report data")?;
+                let mut report =
+                    Report::decode(data.as_ref()).context("Failed to decode report")?;
+                let units = fetch_report_units(&mut conn, row.id).await?;
+                decode_report_units(units, &mut report)?;
+                update_search_index(&mut conn, row.project_id, &row.version, row.id, &report).await
+            }
+            .await;
+            if let Err(e) = result {
+                tracing::warn!("Skipping search index for report {}: {:?}", row.id, e);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Replaces the indexed report for the project version if `report_id` is now its latest report.
+pub(super) async fn update_search_index(
+    conn: &mut SqliteConnection,
+    project_id: i64,
+    version: &str,
+    report_id: i64,
+    report: &Report,
+) -> Result<()> {
+    let latest_id = sqlx::query!(
+        r#"
+        SELECT id AS "id!"
+        FROM reports
+        WHERE project_id = ? AND version = ? COLLATE NOCASE
+        ORDER BY timestamp DESC
+        LIMIT 1
+        "#,
+        project_id,
+        version,
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .map(|row| row.id);
+    if latest_id != Some(report_id) {
+        return Ok(());
+    }
+    if let Some(previous_id) = sqlx::query!(
+        r#"
+        SELECT report_id
+        FROM search_reports
+        WHERE project_id = ? AND version = ?
+        "#,
+        project_id,
+        version,
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .map(|row| row.report_id)
+    {
+        delete_search_entries(conn, previous_id).await?;
+    }
+    for unit in &report.units {
+        let fuzzy_match_percent = unit.measures.as_ref().map(|m| m.fuzzy_match_percent);
+        sqlx::query!(
+            r#"
+            INSERT INTO search_index (name, kind, unit_name, report_id, fuzzy_match_percent)
+            VALUES (?, 'unit', ?, ?, ?)
+            "#,
+            unit.name,
+            unit.name,
+            report_id,
+            fuzzy_match_percent,
+        )
+        .execute(&mut *conn)
+        .await?;
+        for function in &unit.functions {
+            let demangled_name = function.metadata.as_ref().and_then(|m| m.demangled_name.as_ref());
+            sqlx::query!(
+                r#"
+                INSERT INTO search_index
+                    (name, demangled_name, kind, unit_name, report_id, fuzzy_match_percent)
+                VALUES (?, ?, 'function', ?, ?, ?)
+                "#,
+                function.name,
+                demangled_name,
+                unit.name,
+                report_id,
+                function.fuzzy_match_percent,
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+    }
+    sqlx::query!(
+        r#"
+        INSERT INTO search_reports (project_id, version, report_id)
+        VALUES (?, ?, ?)
+        ON CONFLICT (project_id, version) DO UPDATE
+        SET version = EXCLUDED.version, report_id = EXCLUDED.report_id
+        "#,
+        project_id,
+        version,
+        report_id,
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Removes a report's entries from the search index.
+pub(super) async fn delete_search_entries(
+    conn: &mut SqliteConnection,
+    report_id: i64,
+) -> Result<()> {
+    sqlx::query!(
+        r#"
+        DELETE FROM search_index
+        WHERE report_id = ?
+        "#,
+        report_id,
+    )
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query!(
+        r#"
+        DELETE FROM search_reports
+        WHERE report_id = ?
+        "#,
+        report_id,
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
@@ -9,15 +9,17 @@ use axum::http::StatusCode;
 use moka::future::Cache;
 use objdiff_core::bindings::report::Report;
 use octocrab::{
-    models::{repos::RepoCommitPage, ArtifactId, Author, RunId},
+    models::{repos::RepoCommitPage, workflows::Run, ArtifactId, Author, RunId},
     params::actions::ArchiveFormat,
-    GitHubError, Octocrab,
+    GitHubError, Octocrab, Page,
 };
 use regex::Regex;
+use serde::Serialize;
 use tokio::{sync::Semaphore, task::JoinSet};
 
 use crate::{
     config::AppConfig,
+    db::Database,
     models::{Commit, Project, ReportFile},
     AppState,
 };
@@ -218,6 +220,62 @@ pub async fn run(state: &mut AppState, owner: &str, repo: &str, stop_run_id: u64
     Ok(())
 }
 
+/// Searches the project's workflow runs for the given commit and re-inserts its reports.
+/// Returns `false` if no run or report artifacts were found for the commit.
+pub async fn refetch_commit(
+    github: &GitHub,
+    db: &mut Database,
+    project: &Project,
+    sha: &str,
+) -> Result<bool> {
+    tracing::info!("Re-fetching {}/{} commit {}", project.owner, project.repo, sha);
+    #[derive(Serialize)]
+    struct ListRunsParams<'a> {
+        head_sha: &'a str,
+        event: &'static str,
+        status: &'static str,
+        exclude_pull_requests: bool,
+    }
+    // Filter by commit rather than paging through the project's entire run history.
+    // octocrab's builder doesn't support head_sha, so the endpoint is called directly.
+    let route =
+        format!("/repos/{}/{}/actions/workflows/build.yml/runs", project.owner, project.repo);
+    let params = ListRunsParams {
+        head_sha: sha,
+        event: "push",
+        status: "completed",
+        exclude_pull_requests: true,
+    };
+    let items = match github.client.get::<Page<Run>, _, _>(route, Some(&params)).await {
+        Ok(result) => result.items,
+        Err(octocrab::Error::GitHub {
+            source: GitHubError { status_code: StatusCode::NOT_FOUND, .. },
+            ..
+        }) => return Ok(false),
+        Err(e) => return Err(e).context("Failed to fetch workflow runs"),
+    };
+    let Some(run) = items.into_iter().find(|run| run.head_sha.eq_ignore_ascii_case(sha)) else {
+        return Ok(false);
+    };
+    let commit = Commit::from(&run.head_commit);
+    let ProcessWorkflowRunResult { artifacts } =
+        process_workflow_run(github.clone(), project.clone(), run.id).await?;
+    if artifacts.is_empty() {
+        return Ok(false);
+    }
+    for artifact in artifacts {
+        let file = ReportFile {
+            project: project.clone(),
+            commit: commit.clone(),
+            version: artifact.version,
+            report: artifact.report,
+        };
+        db.insert_report(&file).await?;
+        tracing::info!("Inserted report {} ({})", file.version, file.commit.sha);
+    }
+    Ok(true)
+}
+
 struct ProcessWorkflowRunResult {
     artifacts: Vec<ProcessArtifactResult>,
 }
@@ -254,6 +312,10 @@ async fn process_workflow_run(
     static REGEX: OnceLock<Regex> = OnceLock::new();
     let regex = REGEX
         .get_or_init(|| Regex::new(r"^(?P<version>[A-z0-9_\-]+)[_-]report(?:[_-].*)?$").unwrap());
+    // bfbb compatibility
+    static MAPS_REGEX: OnceLock<Regex> = OnceLock::new();
+    let maps_regex =
+        MAPS_REGEX.get_or_init(|| Regex::new(r"^(?P<version>[A-z0-9_\-]+)_maps$").unwrap());
     let sem = Arc::new(Semaphore::new(3));
     let mut set = JoinSet::new();
     struct TaskResult {
@@ -267,9 +329,6 @@ async fn process_workflow_run(
                 version.as_str().to_string()
             } else if artifact_name == "progress" || artifact_name == "progress.json" {
                 // bfbb compatibility
-                static MAPS_REGEX: OnceLock<Regex> = OnceLock::new();
-                let maps_regex = MAPS_REGEX
-                    .get_or_init(|| Regex::new(r"^(?P<version>[A-z0-9_\-]+)_maps$").unwrap());
                 if let Some(version) = artifacts.iter().find_map(|a| {
                     maps_regex
                         .captures(&a.name)
@@ -0,0 +1,347 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{any, get},
+    Json, Router,
+};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+use super::{
+    graphql, project,
+    report::{self, ReportParams, ReportQuery},
+    AppError, FullUri,
+};
+use crate::AppState;
+
+/// Maximum error body size to include in the JSON error message.
+const MAX_ERROR_BODY: usize = 64 * 1024;
+
+pub fn build_router() -> Router<AppState> {
+    Router::new()
+        .route("/openapi.json", get(get_openapi))
+        .route("/graphql", get(graphql::get_graphql).post(graphql::post_graphql))
+        .route("/graphql/schema.graphql", get(graphql::get_schema))
+        .route("/projects", get(get_projects))
+        .route("/projects/:owner/:repo", get(get_project))
+        .route("/projects/:owner/:repo/:version/:commit", get(get_report))
+        .route("/projects/:owner/:repo/:version/:commit/units", get(get_units))
+        .route("/projects/:owner/:repo/:version/:commit/functions", get(get_functions))
+        .route("/projects/:owner/:repo/:version/:commit/history", get(get_history))
+        .route("/projects/:owner/:repo/:version/:commit/badge", get(get_badge))
+        // A catch-all route rather than a fallback, so that unknown API paths aren't matched
+        // by the report routes
+        .route("/*path", any(|| async { AppError::Status(StatusCode::NOT_FOUND) }))
+        .layer(middleware::map_response(json_errors))
+}
+
+#[derive(Serialize)]
+struct ApiError {
+    error: ApiErrorBody,
+}
+
+#[derive(Serialize)]
+struct ApiErrorBody {
+    status: u16,
+    message: String,
+}
+
+/// Replaces the plain-text body of error responses with a JSON error object.
+async fn json_errors(response: Response) -> Response {
+    let status = response.status();
+    if !status.is_client_error() && !status.is_server_error() {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let message = to_bytes(body, MAX_ERROR_BODY)
+        .await
+        .ok()
+        .map(|bytes| String::from_utf8_lossy(&bytes).trim().to_string())
+        .filter(|message| !message.is_empty())
+        .unwrap_or_else(|| status.canonical_reason().unwrap_or("Unknown error").to_string());
+    let body =
+        serde_json::to_vec(&ApiError { error: ApiErrorBody { status: status.as_u16(), message } })
+            .unwrap_or_default();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.insert(header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref().parse().unwrap());
+    Response::from_parts(parts, Body::from(body))
+}
+
+async fn get_projects(
+    state: State<AppState>,
+    headers: HeaderMap,
+    uri: FullUri,
+    query: Query<project::ProjectsQuery>,
+) -> Result<Response, AppError> {
+    project::get_projects_api(state, headers, uri, query).await
+}
+
+async fn get_project(
+    Path((owner, repo)): Path<(String, String)>,
+    State(state): State<AppState>,
+) -> Result<Response, AppError> {
+    let Some(project_info) = state.db.get_project_info(&owner, &repo, None).await? else {
+        return Err(AppError::Status(StatusCode::NOT_FOUND));
+    };
+    Ok(Json(project_info).into_response())
+}
+
+async fn get_report(
+    Path(params): Path<ReportParams>,
+    Query(query): Query<ReportQuery>,
+    headers: HeaderMap,
+    FullUri(uri): FullUri,
+    State(state): State<AppState>,
+) -> Result<Response, AppError> {
+    report::get_report_json(state, params, query, "report", uri, headers).await
+}
+
+async fn get_units(
+    Path(params): Path<ReportParams>,
+    Query(query): Query<ReportQuery>,
+    headers: HeaderMap,
+    FullUri(uri): FullUri,
+    State(state): State<AppState>,
+) -> Result<Response, AppError> {
+    if query.unit().is_some() {
+        return Err(AppError::Status(StatusCode::BAD_REQUEST));
+    }
+    report::get_report_json(state, params, query, "units", uri, headers).await
+}
+
+async fn get_functions(
+    Path(params): Path<ReportParams>,
+    Query(query): Query<ReportQuery>,
+    headers: HeaderMap,
+    FullUri(uri): FullUri,
+    State(state): State<AppState>,
+) -> Result<Response, AppError> {
+    if query.unit().is_none() {
+        return Err(AppError::Status(StatusCode::BAD_REQUEST));
+    }
+    report::get_report_json(state, params, query, "units", uri, headers).await
+}
+
+async fn get_history(
+    Path(params): Path<ReportParams>,
+    Query(query): Query<ReportQuery>,
+    headers: HeaderMap,
+    FullUri(uri): FullUri,
+    State(state): State<AppState>,
+) -> Result<Response, AppError> {
+    report::get_report_json(state, params, query, "history", uri, headers).await
+}
+
+async fn get_badge(
+    Path(params): Path<ReportParams>,
+    Query(query): Query<ReportQuery>,
+    headers: HeaderMap,
+    FullUri(uri): FullUri,
+    State(state): State<AppState>,
+) -> Result<Response, AppError> {
+    report::get_report_json(state, params, query, "shield", uri, headers).await
+}
+
+struct Endpoint {
+    path: &'static str,
+    summary: &'static str,
+    params: &'static [Param],
+}
+
+struct Param {
+    name: &'static str,
+    location: &'static str,
+    description: &'static str,
+    required: bool,
+}
+
+const fn path(name: &'static str, description: &'static str) -> Param {
+    Param { name, location: "path", description, required: true }
+}
+
+const fn query(name: &'static str, description: &'static str) -> Param {
+    Param { name, location: "query", description, required: false }
+}
+
+const fn required_query(name: &'static str, description: &'static str) -> Param {
+    Param { name, location: "query", description, required: true }
+}
+
+const OWNER: Param = path("owner", "Repository owner");
+const REPO: Param = path("repo", "Repository name");
+const VERSION: Param = path("version", "Report version, or `default`");
+const COMMIT: Param = path("commit", "Commit SHA, or `latest`");
+const CATEGORY: Param = query("category", "Progress category ID");
+
+const ENDPOINTS: &[Endpoint] = &[
+    Endpoint {
+        path: "/projects",
+        summary: "List projects with their latest measures",
+        params: &[
+            query(
+                "sort",
+                "updated, matched_code, matched_data, complete_units, recent_gain or name",
+            ),
+            query("platform", "Platform ID"),
+            query("q", "Filter by name"),
+            query("min", "Minimum matched code percentage"),
+            query("max", "Maximum matched code percentage"),
+        ],
+    },
+    Endpoint {
+        path: "/projects/{owner}/{repo}",
+        summary: "Get a project and its latest commit and versions",
+        params: &[OWNER, REPO],
+    },
+    Endpoint {
+        path: "/projects/{owner}/{repo}/{version}/{commit}",
+        summary: "Get a full report",
+        params: &[OWNER, REPO, VERSION, COMMIT],
+    },
+    Endpoint {
+        path: "/projects/{owner}/{repo}/{version}/{commit}/units",
+        summary: "List the units in a report",
+        params: &[
+            OWNER,
+            REPO,
+            VERSION,
+            COMMIT,
+            CATEGORY,
+            query("path", "Directory path to list"),
+            query("sort", "size, unmatched, fuzzy or name"),
+            query("order", "asc or desc"),
+            query("q", "Filter by name"),
+            query("complete", "Filter by completeness"),
+            query("page", "Page number, starting at 1"),
+            query("limit", "Units per page"),
+        ],
+    },
+    Endpoint {
+        path: "/projects/{owner}/{repo}/{version}/{commit}/functions",
+        summary: "List the functions in a unit",
+        params: &[
+            OWNER,
+            REPO,
+            VERSION,
+            COMMIT,
+            required_query("unit", "Unit name"),
+            query("sort", "size, unmatched, fuzzy or name"),
+            query("order", "asc or desc"),
+            query("q", "Filter by name"),
+            query("complete", "Filter by completeness"),
+            query("page", "Page number, starting at 1"),
+            query("limit", "Functions per page"),
+        ],
+    },
+    Endpoint {
+        path: "/projects/{owner}/{repo}/{version}/{commit}/history",
+        summary: "List the commits where a unit or function changed",
+        params: &[
+            OWNER,
+            REPO,
+            VERSION,
+            COMMIT,
+            required_query("unit", "Unit name"),
+            query("function", "Function name"),
+            query("before", "Only list changes before this commit, from `next_before`"),
+            query("limit", "Unit revisions to scan per page"),
+        ],
+    },
+    Endpoint {
+        path: "/projects/{owner}/{repo}/{version}/{commit}/badge",
+        summary: "Get a shields.io endpoint badge",
+        params: &[
+            OWNER,
+            REPO,
+            VERSION,
+            COMMIT,
+            CATEGORY,
+            query("unit", "Unit name"),
+            query("function", "Function name"),
+            query("path", "Directory path"),
+            query("measure", "Comma-separated measures"),
+            query("since", "Show the change since `{N}d` days ago or a full commit SHA"),
+            query("color", "Badge color, or `auto`"),
+            query("thresholds", "Comma-separated `{min}:{color}` pairs for `color=auto`"),
+            query("message", "Message template with `{measure}` and `{measure:delta}`"),
+            query("label", "Badge label"),
+            query("labelColor", "Label color"),
+            query("style", "Badge style"),
+        ],
+    },
+];
+
+/// Generates the OpenAPI document from the endpoint table.
+fn openapi() -> Value {
+    let mut paths = Map::new();
+    for endpoint in ENDPOINTS {
+        let parameters = endpoint
+            .params
+            .iter()
+            .map(|p| {
+                json!({
+                    "name": p.name,
+                    "in": p.location,
+                    "description": p.description,
+                    "required": p.required,
+                    "schema": { "type": "string" },
+                })
+            })
+            .collect::<Vec<_>>();
+        paths.insert(
+            endpoint.path.to_string(),
+            json!({
+                "get": {
+                    "summary": endpoint.summary,
+                    "parameters": parameters,
+                    "responses": {
+                        "200": {
+                            "description": "Success",
+                            "content": { "application/json": {} },
+                        },
+                        "default": {
+                            "description": "Error",
+                            "content": {
+                                "application/json": {
+                                    "schema": { "$ref": "#/components/schemas/Error" },
+                                },
+                            },
+                        },
+                    },
+                },
+            }),
+        );
+    }
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "decomp.dev API",
+            "version": "1",
+        },
+        "servers": [{ "url": "/api/v1" }],
+        "paths": paths,
+        "components": {
+            "schemas": {
+                "Error": {
+                    "type": "object",
+                    "properties": {
+                        "error": {
+                            "type": "object",
+                            "properties": {
+                                "status": { "type": "integer" },
+                                "message": { "type": "string" },
+                            },
+                            "required": ["status", "message"],
+                        },
+                    },
+                    "required": ["error"],
+                },
+            },
+        },
+    })
+}
+
+async fn get_openapi() -> impl IntoResponse { Json(openapi()) }
@@ -1,9 +1,25 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
+use axum::http::StatusCode;
 use image::ImageFormat;
 use objdiff_core::bindings::report::Measures;
 use serde::{Deserialize, Serialize};
 
-use crate::svg;
+use super::AppError;
+use crate::{
+    blocking::{BlockingPool, Task},
+    svg,
+    util::format_bytes,
+};
+
+/// Colours used by `color=auto`, keyed by the minimum percentage.
+const DEFAULT_THRESHOLDS: &[(f32, &str)] = &[
+    (0.0, "red"),
+    (25.0, "orange"),
+    (50.0, "yellow"),
+    (75.0, "yellowgreen"),
+    (90.0, "green"),
+    (100.0, "brightgreen"),
+];
 
 #[derive(Deserialize, Default, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -12,7 +28,42 @@ pub struct ShieldParams {
     label_color: Option<String>,
     color: Option<String>,
     style: Option<String>,
+    /// Comma-separated list of measures
     measure: Option<String>,
+    /// Show the change since `{N}d` days ago or since a full commit SHA
+    since: Option<String>,
+    /// Comma-separated `{min}:{color}` pairs used by `color=auto`
+    thresholds: Option<String>,
+    /// Message template, with `{measure}` and `{measure:delta}` placeholders
+    message: Option<String>,
+}
+
+/// The furthest back, in days, that a shield's deltas can be relative to.
+const MAX_SINCE_DAYS: u32 = 36500;
+
+/// The baseline that a shield's deltas are relative to.
+#[derive(Debug, PartialEq)]
+pub enum Since<'a> {
+    Days(u32),
+    /// A full commit SHA
+    Commit(&'a str),
+}
+
+impl ShieldParams {
+    pub fn since(&self) -> Result<Option<Since<'_>>> {
+        let Some(since) = self.since.as_deref() else {
+            return Ok(None);
+        };
+        if since.len() == 40 && since.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Ok(Some(Since::Commit(since)));
+        }
+        let days = since
+            .strip_suffix(['d', 'D'])
+            .and_then(|days| days.parse::<u32>().ok())
+            .filter(|&days| days <= MAX_SINCE_DAYS)
+            .ok_or_else(|| anyhow!("Invalid since: {}", since))?;
+        Ok(Some(Since::Days(days)))
+    }
 }
 
 #[derive(Serialize, Clone)]
@@ -29,30 +80,211 @@ pub struct ShieldResponse {
     label_color: Option<String>,
 }
 
+#[derive(Copy, Clone)]
+enum Measure {
+    Code,
+    Data,
+    Functions,
+    CompleteCode,
+    CompleteData,
+    CompleteUnits,
+    Fuzzy,
+    MatchedFunctionsPercent,
+    MatchedData,
+}
+
+impl Measure {
+    fn from_str(s: &str) -> Option<Self> {
+        Some(match s {
+            "code" => Self::Code,
+            "data" => Self::Data,
+            "functions" => Self::Functions,
+            "complete_code" => Self::CompleteCode,
+            "complete_data" => Self::CompleteData,
+            "complete_units" => Self::CompleteUnits,
+            "fuzzy" => Self::Fuzzy,
+            "matched_functions_percent" => Self::MatchedFunctionsPercent,
+            "matched_data" => Self::MatchedData,
+            _ => return None,
+        })
+    }
+
+    fn value(self, measures: &Measures) -> MeasureValue {
+        match self {
+            Self::Code => MeasureValue::Percent(measures.matched_code_percent),
+            Self::Data => MeasureValue::Percent(measures.matched_data_percent),
+            Self::Functions => MeasureValue::Count(
+                measures.matched_functions as u64,
+                measures.total_functions as u64,
+            ),
+            Self::CompleteCode => MeasureValue::Percent(measures.complete_code_percent),
+            Self::CompleteData => MeasureValue::Percent(measures.complete_data_percent),
+            Self::CompleteUnits => {
+                MeasureValue::Count(measures.complete_units as u64, measures.total_units as u64)
+            }
+            Self::Fuzzy => MeasureValue::Percent(measures.fuzzy_match_percent),
+            Self::MatchedFunctionsPercent => {
+                MeasureValue::Percent(measures.matched_functions_percent)
+            }
+            Self::MatchedData => MeasureValue::Bytes(measures.matched_data, measures.total_data),
+        }
+    }
+}
+
+enum MeasureValue {
+    Percent(f32),
+    /// Matched and total counts
+    Count(u64, u64),
+    /// Matched and total bytes
+    Bytes(u64, u64),
+}
+
+impl MeasureValue {
+    fn percent(&self) -> f32 {
+        match *self {
+            Self::Percent(percent) => percent,
+            Self::Count(_, 0) | Self::Bytes(_, 0) => 100.0,
+            Self::Count(matched, total) | Self::Bytes(matched, total) => {
+                matched as f32 / total as f32 * 100.0
+            }
+        }
+    }
+
+    fn format(&self) -> String {
+        match *self {
+            Self::Percent(percent) => format!("{:.2}%", percent),
+            Self::Count(matched, total) => format!("{}/{}", matched, total),
+            Self::Bytes(matched, _) => format_bytes(matched),
+        }
+    }
+
+    fn format_delta(&self, baseline: &MeasureValue) -> String {
+        match (self, baseline) {
+            (Self::Count(matched, _), Self::Count(prev, _)) => {
+                format!("{:+}", *matched as i64 - *prev as i64)
+            }
+            (Self::Bytes(matched, _), Self::Bytes(prev, _)) => {
+                let delta = *matched as i64 - *prev as i64;
+                let sign = if delta < 0 { '-' } else { '+' };
+                format!("{}{}", sign, format_bytes(delta.unsigned_abs()))
+            }
+            _ => format!("{:+.2}%", self.percent() - baseline.percent()),
+        }
+    }
+}
+
+/// Formats a measure, followed by its change since the baseline if there is one.
+fn format_measure(measure: Measure, measures: &Measures, baseline: Option<&Measures>) -> String {
+    let value = measure.value(measures);
+    match baseline {
+        Some(baseline) => {
+            format!("{} ({})", value.format(), value.format_delta(&measure.value(baseline)))
+        }
+        None => value.format(),
+    }
+}
+
+/// Substitutes `{measure}` and `{measure:delta}` placeholders in a message template.
+fn render_template(
+    template: &str,
+    measures: &Measures,
+    baseline: Option<&Measures>,
+) -> Result<String> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let end = rest[start..].find('}').ok_or_else(|| anyhow!("Unclosed placeholder"))?;
+        let placeholder = &rest[start + 1..start + end];
+        let (name, delta) = match placeholder.split_once(':') {
+            Some((name, "delta")) => (name, true),
+            Some(_) => bail!("Unknown placeholder: {}", placeholder),
+            None => (placeholder, false),
+        };
+        let measure =
+            Measure::from_str(name).ok_or_else(|| anyhow!("Unknown measure: {}", name))?;
+        let value = measure.value(measures);
+        if delta {
+            let baseline = baseline.ok_or_else(|| anyhow!("Delta requires since"))?;
+            out.push_str(&value.format_delta(&measure.value(baseline)));
+        } else {
+            out.push_str(&value.format());
+        }
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn auto_color(percent: f32, thresholds: Option<&str>) -> Result<String> {
+    let Some(thresholds) = thresholds else {
+        return Ok(DEFAULT_THRESHOLDS
+            .iter()
+            .rev()
+            .find(|(min, _)| percent >= *min)
+            .map_or("red", |(_, color)| color)
+            .to_string());
+    };
+    let mut best: Option<(f32, &str)> = None;
+    for threshold in thresholds.split(',') {
+        let (min, color) =
+            threshold.split_once(':').ok_or_else(|| anyhow!("Invalid threshold: {}", threshold))?;
+        let min = min.trim().parse::<f32>()?;
+        if percent >= min && best.is_none_or(|(best_min, _)| min > best_min) {
+            best = Some((min, color.trim()));
+        }
+    }
+    Ok(best.map_or("lightgrey", |(_, color)| color).to_string())
+}
+
+/// Renders a shield for `measures`. If `baseline` is given, the message includes each
+/// measure's change since then. Invalid parameters are a bad request.
 pub fn render(
     measures: &Measures,
+    baseline: Option<&Measures>,
+    default_label: &str,
+    params: &ShieldParams,
+) -> Result<ShieldResponse, AppError> {
+    shield_response(measures, baseline, default_label, params)
+        .map_err(|_| AppError::Status(StatusCode::BAD_REQUEST))
+}
+
+fn shield_response(
+    measures: &Measures,
+    baseline: Option<&Measures>,
     default_label: &str,
     params: &ShieldParams,
 ) -> Result<ShieldResponse> {
     let label = params.label.clone().unwrap_or_else(|| default_label.to_string());
-    let message = if let Some(measure) = &params.measure {
-        match measure.as_str() {
-            "code" => format!("{:.2}%", measures.matched_code_percent),
-            "data" => format!("{:.2}%", measures.matched_data_percent),
-            "functions" => format!("{}/{}", measures.matched_functions, measures.total_functions),
-            "complete_code" => format!("{:.2}%", measures.complete_code_percent),
-            "complete_data" => format!("{:.2}%", measures.complete_data_percent),
-            "complete_units" => format!("{}/{}", measures.complete_units, measures.total_units),
-            _ => return Err(anyhow!("Unknown measure")),
-        }
+    let selected = match &params.measure {
+        Some(measure) => measure
+            .split(',')
+            .map(|m| Measure::from_str(m.trim()).ok_or_else(|| anyhow!("Unknown measure")))
+            .collect::<Result<Vec<_>>>()?,
+        None => vec![Measure::Code],
+    };
+    let message = if let Some(template) = &params.message {
+        render_template(template, measures, baseline)?
     } else {
-        format!("{:.2}%", measures.matched_code_percent)
+        selected
+            .iter()
+            .map(|&m| format_measure(m, measures, baseline))
+            .collect::<Vec<_>>()
+            .join(" | ")
+    };
+    let color = match params.color.as_deref() {
+        Some("auto") => {
+            let percent = selected.first().map_or(0.0, |m| m.value(measures).percent());
+            auto_color(percent, params.thresholds.as_deref())?
+        }
+        Some(color) => color.to_string(),
+        None => "informational".to_string(),
     };
     Ok(ShieldResponse {
         schema_version: 1,
         label,
         message,
-        color: Some(params.color.clone().unwrap_or_else(|| "informational".to_string())),
+        color: Some(color),
         style: params.style.clone(),
         label_color: params.label_color.clone(),
     })
@@ -60,10 +292,11 @@ pub fn render(
 
 pub fn render_svg(
     measures: &Measures,
+    baseline: Option<&Measures>,
     default_label: &str,
     params: &ShieldParams,
-) -> Result<String> {
-    let response = render(measures, default_label, params)?;
+) -> Result<String, AppError> {
+    let response = render(measures, baseline, default_label, params)?;
     let mut builder = badge_maker::BadgeBuilder::new();
     builder.label(&response.label).message(&response.message);
     if let Some(color) = &response.color {
@@ -75,16 +308,82 @@ pub fn render_svg(
     if let Some(label_color) = &response.label_color {
         builder.label_color_parse(label_color);
     }
-    let badge = builder.build()?;
+    // Fails on an invalid colour or style
+    let badge = builder.build().map_err(|_| AppError::Status(StatusCode::BAD_REQUEST))?;
     Ok(badge.svg())
 }
 
-pub fn render_image(
+pub async fn render_image(
     measures: &Measures,
+    baseline: Option<&Measures>,
     default_label: &str,
     params: &ShieldParams,
+    blocking: &BlockingPool,
     format: ImageFormat,
-) -> Result<Vec<u8>> {
-    let svg = render_svg(measures, default_label, params)?;
-    svg::render_image(&svg, format)
+) -> Result<Vec<u8>, AppError> {
+    let svg = render_svg(measures, baseline, default_label, params)?;
+    Ok(blocking.run(Task::Render, move || svg::render_image(&svg, format)).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(since: &str) -> ShieldParams {
+        ShieldParams { since: Some(since.to_string()), ..Default::default() }
+    }
+
+    #[test]
+    fn parses_since_days() {
+        assert_eq!(ShieldParams::default().since().unwrap(), None);
+        assert_eq!(params("0d").since().unwrap(), Some(Since::Days(0)));
+        assert_eq!(params("30d").since().unwrap(), Some(Since::Days(30)));
+        assert_eq!(params("7D").since().unwrap(), Some(Since::Days(7)));
+        assert_eq!(params("1234d").since().unwrap(), Some(Since::Days(1234)));
+        assert_eq!(params("36500d").since().unwrap(), Some(Since::Days(MAX_SINCE_DAYS)));
+        assert!(params("36501d").since().is_err());
+        assert!(params("100000000d").since().is_err());
+        assert!(params("-1d").since().is_err());
+        assert!(params("d").since().is_err());
+        assert!(params("30").since().is_err());
+    }
+
+    #[test]
+    fn parses_since_commit() {
+        let sha = "0123456789abcdef0123456789ABCDEF0123456d";
+        assert_eq!(params(sha).since().unwrap(), Some(Since::Commit(sha)));
+        // Short SHAs can't be looked up
+        assert!(params("0123456").since().is_err());
+        assert!(params(&sha[1..]).since().is_err());
+        assert!(params("g123456789abcdef0123456789abcdef01234567").since().is_err());
+        assert!(params("").since().is_err());
+    }
+
+    #[test]
+    fn auto_color_uses_default_thresholds() {
+        assert_eq!(auto_color(0.0, None).unwrap(), "red");
+        assert_eq!(auto_color(24.9, None).unwrap(), "red");
+        assert_eq!(auto_color(25.0, None).unwrap(), "orange");
+        assert_eq!(auto_color(89.9, None).unwrap(), "yellowgreen");
+        assert_eq!(auto_color(99.99, None).unwrap(), "green");
+        assert_eq!(auto_color(100.0, None).unwrap(), "brightgreen");
+        assert_eq!(auto_color(-1.0, None).unwrap(), "red");
+    }
+
+    #[test]
+    fn auto_color_uses_custom_thresholds() {
+        let thresholds = Some("50:blue, 0:red ,80:#00ff00");
+        assert_eq!(auto_color(10.0, thresholds).unwrap(), "red");
+        assert_eq!(auto_color(50.0, thresholds).unwrap(), "blue");
+        assert_eq!(auto_color(95.0, thresholds).unwrap(), "#00ff00");
+        // Below every threshold
+        assert_eq!(auto_color(5.0, Some("10:red")).unwrap(), "lightgrey");
+    }
+
+    #[test]
+    fn auto_color_rejects_invalid_thresholds() {
+        assert!(auto_color(50.0, Some("50")).is_err());
+        assert!(auto_color(50.0, Some("half:blue")).is_err());
+        assert!(auto_color(50.0, Some("50:blue,")).is_err());
+    }
 }
@@ -0,0 +1,162 @@
+use anyhow::Result;
+use image::ImageFormat;
+use objdiff_core::bindings::report::{Measures, ReportCategory};
+use serde::Serialize;
+
+use crate::{blocking::Task, handlers::treemap::Theme, svg, templates::render, AppState};
+
+pub const DEFAULT_BAR_WIDTH: u32 = 400;
+const ROW_HEIGHT: u32 = 28;
+const PADDING: f32 = 4.0;
+/// Average glyph width relative to the font size, used to size the label columns.
+const CHAR_WIDTH: f32 = 0.7;
+/// Widest value label, e.g. "100.00%".
+const VALUE_CHARS: usize = 7;
+// Matches the progress bars on the report page
+const CODE_COLORS: [&str; 3] = ["#00a06c", "#007a50", "#00c88a"];
+const DATA_COLORS: [&str; 2] = ["#0172ad", "#015887"];
+
+/// Options for a progress bar render.
+pub struct BarOptions {
+    pub w: u32,
+    pub h: Option<u32>,
+    pub theme: Option<Theme>,
+    /// Adds a code bar for each progress category
+    pub categories: bool,
+}
+
+#[derive(Serialize)]
+struct BarTemplateContext {
+    w: u32,
+    h: u32,
+    background: Option<&'static str>,
+    text: &'static str,
+    track: &'static str,
+    font_size: f32,
+    rows: Vec<BarRow>,
+}
+
+#[derive(Serialize)]
+struct BarRow {
+    label: String,
+    value: String,
+    y: f32,
+    h: f32,
+    label_x: f32,
+    bar_x: f32,
+    bar_w: f32,
+    value_x: f32,
+    sections: Vec<BarSection>,
+}
+
+#[derive(Serialize)]
+struct BarSection {
+    x: f32,
+    w: f32,
+    color: &'static str,
+    striped: bool,
+}
+
+/// Renders stacked progress bars for code and data, and optionally each category's code.
+pub fn render_svg(
+    measures: &Measures,
+    categories: &[ReportCategory],
+    options: &BarOptions,
+    state: &AppState,
+) -> Result<String> {
+    let mut bars = vec![
+        ("Code".to_string(), code_sections(measures), measures.matched_code_percent),
+        ("Data".to_string(), data_sections(measures), measures.matched_data_percent),
+    ];
+    if options.categories {
+        for category in categories {
+            let measures = category.measures.unwrap_or_default();
+            bars.push((
+                category.name.clone(),
+                code_sections(&measures),
+                measures.matched_code_percent,
+            ));
+        }
+    }
+    let h = options.h.unwrap_or(ROW_HEIGHT * bars.len() as u32);
+    let row_h = h as f32 / bars.len() as f32;
+    let font_size = (row_h * 0.5).clamp(6.0, 24.0);
+    let char_w = font_size * CHAR_WIDTH;
+    let label_chars = bars.iter().map(|(label, _, _)| label.chars().count()).max().unwrap_or(0);
+    let label_w = label_chars as f32 * char_w + PADDING * 2.0;
+    let value_w = VALUE_CHARS as f32 * char_w + PADDING * 2.0;
+    let bar_x = label_w;
+    let bar_w = (options.w as f32 - label_w - value_w).max(0.0);
+    let rows = bars
+        .into_iter()
+        .enumerate()
+        .map(|(i, (label, sections, percent))| BarRow {
+            label,
+            value: format!("{:.2}%", percent),
+            y: i as f32 * row_h + PADDING / 2.0,
+            h: (row_h - PADDING).max(1.0),
+            label_x: PADDING,
+            bar_x,
+            bar_w,
+            value_x: options.w as f32 - PADDING,
+            sections: layout_sections(&sections, bar_x, bar_w),
+        })
+        .collect();
+    let (text, track) = match options.theme {
+        Some(Theme::Dark) => ("#ffffff", "#333c4e"),
+        Some(Theme::Light) => ("#000000", "#f1f5f9"),
+        // Readable on both light and dark backgrounds
+        None => ("#8891a4", "#8891a433"),
+    };
+    render(&state.templates, "bar.svg", BarTemplateContext {
+        w: options.w,
+        h,
+        background: options.theme.map(Theme::background),
+        text,
+        track,
+        font_size,
+        rows,
+    })
+}
+
+pub async fn render_image(
+    measures: &Measures,
+    categories: &[ReportCategory],
+    options: &BarOptions,
+    state: &AppState,
+    format: ImageFormat,
+) -> Result<Vec<u8>> {
+    let svg = render_svg(measures, categories, options, state)?;
+    state.blocking.run(Task::Render, move || svg::render_image(&svg, format)).await
+}
+
+/// Fully linked, perfectly matched and fuzzy matched code, as (percent, color, striped).
+fn code_sections(measures: &Measures) -> Vec<(f32, &'static str, bool)> {
+    vec![
+        (measures.complete_code_percent, CODE_COLORS[0], false),
+        (measures.matched_code_percent - measures.complete_code_percent, CODE_COLORS[1], true),
+        (measures.fuzzy_match_percent - measures.matched_code_percent, CODE_COLORS[2], true),
+    ]
+}
+
+/// Fully linked and perfectly matched data, as (percent, color, striped).
+fn data_sections(measures: &Measures) -> Vec<(f32, &'static str, bool)> {
+    vec![
+        (measures.complete_data_percent, DATA_COLORS[0], false),
+        (measures.matched_data_percent - measures.complete_data_percent, DATA_COLORS[1], true),
+    ]
+}
+
+fn layout_sections(sections: &[(f32, &'static str, bool)], x: f32, w: f32) -> Vec<BarSection> {
+    let mut offset = 0.0;
+    sections
+        .iter()
+        .filter_map(|&(percent, color, striped)| {
+            let percent = percent.clamp(0.0, 100.0 - offset);
+            let section =
+                BarSection { x: x + offset / 100.0 * w, w: percent / 100.0 * w, color, striped };
+            offset += percent;
+            (section.w > 0.0).then_some(section)
+        })
+        .collect()
+}
@@ -0,0 +1,355 @@
+use std::{
+    fs::{self, File},
+    io,
+    path::Path,
+    sync::OnceLock,
+};
+
+use anyhow::Result;
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode, Uri},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use mime::Mime;
+use tracing::warn;
+
+use crate::templates::TEMPLATE_DIR;
+
+/// Responses for a full commit sha only change when the server is updated, so they're cached
+/// for a day and then revalidated against the build's ETag.
+const PINNED: &str = "public, max-age=86400";
+/// Responses for the latest commit change whenever a new report is uploaded.
+const LATEST: &str = "public, max-age=300";
+
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+static BUILD_ID: OnceLock<blake3::Hash> = OnceLock::new();
+
+#[derive(Debug, Copy, Clone)]
+pub enum CachePolicy {
+    /// The URL addresses a specific commit and version, so the response only changes with the
+    /// server. Its ETag is derived from the request and the build (see [`immutable_etag`]), so
+    /// that it can be checked before the response is rendered.
+    Immutable(blake3::Hash),
+    /// The URL resolves to the latest commit or the default version, last modified at the
+    /// given commit's timestamp.
+    Latest(DateTime<Utc>),
+}
+
+/// Derives the ETag of an immutable response from everything its body depends on: the build,
+/// the URL (with its query parameters sorted) and the negotiated content types.
+pub fn immutable_etag(uri: &Uri, acceptable: &[Mime]) -> blake3::Hash {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(build_id().as_bytes());
+    if let Some(scheme) = uri.scheme_str() {
+        hasher.update(scheme.as_bytes());
+    }
+    hasher.update(b"://");
+    if let Some(authority) = uri.authority() {
+        hasher.update(authority.as_str().as_bytes());
+    }
+    hasher.update(uri.path().as_bytes());
+    let mut pairs = uri.query().unwrap_or_default().split('&').collect::<Vec<_>>();
+    pairs.sort_unstable();
+    for pair in pairs.into_iter().filter(|pair| !pair.is_empty()) {
+        hasher.update(b"\0");
+        hasher.update(pair.as_bytes());
+    }
+    for mime in acceptable {
+        hasher.update(b"\0");
+        hasher.update(mime.as_ref().as_bytes());
+    }
+    hasher.finalize()
+}
+
+/// Identifies the running build by hashing the server binary and the templates it renders
+/// with. If the binary can't be read, the start time is used instead, so that ETags still
+/// change whenever the server is restarted with a new build.
+fn build_id() -> &'static blake3::Hash {
+    BUILD_ID.get_or_init(|| {
+        let mut hasher = blake3::Hasher::new();
+        if let Err(e) =
+            std::env::current_exe().and_then(|path| hasher.update_reader(File::open(path)?))
+        {
+            warn!("Failed to hash server binary: {}", e);
+            hasher.update(Utc::now().to_rfc3339().as_bytes());
+        }
+        if let Err(e) = hash_dir(&mut hasher, Path::new(TEMPLATE_DIR)) {
+            warn!("Failed to hash templates: {}", e);
+        }
+        hasher.finalize()
+    })
+}
+
+/// Hashes the paths and contents of the files under a directory, in a stable order.
+fn hash_dir(hasher: &mut blake3::Hasher, dir: &Path) -> io::Result<()> {
+    let mut paths =
+        fs::read_dir(dir)?.map(|entry| entry.map(|e| e.path())).collect::<io::Result<Vec<_>>>()?;
+    paths.sort();
+    for path in paths {
+        if path.is_dir() {
+            hash_dir(hasher, &path)?;
+        } else {
+            hasher.update(path.to_string_lossy().as_bytes());
+            hasher.update(b"\0");
+            hasher.update_reader(File::open(&path)?)?;
+        }
+    }
+    Ok(())
+}
+
+/// Answers 304 Not Modified up front if the request's If-None-Match matches an immutable
+/// response's ETag, so that the report doesn't need to be loaded or rendered. A wildcard is
+/// left to [`conditional`], since only the rendered response shows whether it exists.
+pub fn check_immutable(headers: &HeaderMap, policy: CachePolicy) -> Result<Option<Response>> {
+    let (CachePolicy::Immutable(hash), Some(if_none_match)) =
+        (policy, headers.get(header::IF_NONE_MATCH))
+    else {
+        return Ok(None);
+    };
+    if if_none_match.as_bytes().trim_ascii() == b"*" {
+        return Ok(None);
+    }
+    let mut cache_headers = HeaderMap::new();
+    insert_cache_headers(&mut cache_headers, policy, &hash)?;
+    if !cache_headers.get(header::ETAG).is_some_and(|etag| etag_matches(if_none_match, etag)) {
+        return Ok(None);
+    }
+    Ok(Some(not_modified(&cache_headers)))
+}
+
+/// Adds a weak ETag and caching headers to a successful response. The ETag is weak because the
+/// compression layer serves the same representation with different content encodings. Unless
+/// the policy provides one, the ETag is a hash of the body. HTML responses are left alone, since
+/// pages link to neighbouring commits and include render timings.
+pub async fn with_cache_headers(response: Response, policy: CachePolicy) -> Result<Response> {
+    let is_html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with(mime::TEXT_HTML.as_ref()));
+    if response.status() != StatusCode::OK || is_html {
+        return Ok(response);
+    }
+    let (mut parts, body) = response.into_parts();
+    let body = match policy {
+        CachePolicy::Immutable(hash) => {
+            insert_cache_headers(&mut parts.headers, policy, &hash)?;
+            body
+        }
+        CachePolicy::Latest(_) => {
+            let bytes = to_bytes(body, usize::MAX).await?;
+            insert_cache_headers(&mut parts.headers, policy, &blake3::hash(&bytes))?;
+            Body::from(bytes)
+        }
+    };
+    Ok(Response::from_parts(parts, body))
+}
+
+fn insert_cache_headers(
+    headers: &mut HeaderMap,
+    policy: CachePolicy,
+    hash: &blake3::Hash,
+) -> Result<()> {
+    let hash = hash.to_hex();
+    headers.insert(header::ETAG, HeaderValue::from_str(&format!("W/\"{}\"", &hash[..32]))?);
+    match policy {
+        CachePolicy::Immutable(_) => {
+            headers.insert(header::CACHE_CONTROL, HeaderValue::from_static(PINNED));
+        }
+        CachePolicy::Latest(timestamp) => {
+            headers.insert(header::CACHE_CONTROL, HeaderValue::from_static(LATEST));
+            headers.insert(
+                header::LAST_MODIFIED,
+                HeaderValue::from_str(&timestamp.format(HTTP_DATE_FORMAT).to_string())?,
+            );
+        }
+    }
+    // Without an extension, the representation depends on the Accept header
+    headers.insert(header::VARY, HeaderValue::from_static("Accept"));
+    Ok(())
+}
+
+/// Middleware that answers conditional GET requests with 304 Not Modified when the response's
+/// ETag or Last-Modified header shows the client's copy is still current.
+pub async fn conditional(request: Request, next: Next) -> Response {
+    if request.method() != Method::GET && request.method() != Method::HEAD {
+        return next.run(request).await;
+    }
+    let if_none_match = request.headers().get(header::IF_NONE_MATCH).cloned();
+    let if_modified_since = request.headers().get(header::IF_MODIFIED_SINCE).cloned();
+    let response = next.run(request).await;
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+    let headers = response.headers();
+    // If-Modified-Since is ignored when If-None-Match is present
+    let fresh = match (if_none_match, if_modified_since) {
+        (Some(if_none_match), _) => {
+            headers.get(header::ETAG).is_some_and(|etag| etag_matches(&if_none_match, etag))
+        }
+        (None, Some(if_modified_since)) => headers
+            .get(header::LAST_MODIFIED)
+            .and_then(parse_http_date)
+            .zip(parse_http_date(&if_modified_since))
+            .is_some_and(|(last_modified, since)| last_modified <= since),
+        (None, None) => false,
+    };
+    if !fresh {
+        return response;
+    }
+    not_modified(headers)
+}
+
+/// Weak comparison, as required for If-None-Match.
+fn etag_matches(if_none_match: &HeaderValue, etag: &HeaderValue) -> bool {
+    let (Ok(if_none_match), Ok(etag)) = (if_none_match.to_str(), etag.to_str()) else {
+        return false;
+    };
+    let etag = etag.trim_start_matches("W/");
+    if_none_match
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/// Parses an HTTP date in the preferred IMF-fixdate format or either of the obsolete RFC 850
+/// and asctime formats, which recipients must also accept.
+fn parse_http_date(value: &HeaderValue) -> Option<DateTime<Utc>> {
+    let value = value.to_str().ok()?.trim();
+    [HTTP_DATE_FORMAT, "%A, %d-%b-%y %H:%M:%S GMT", "%a %b %e %H:%M:%S %Y"]
+        .into_iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .map(|date| date.and_utc())
+}
+
+/// Builds a 304 response, repeating the headers it must share with the 200 response it stands
+/// in for.
+fn not_modified(headers: &HeaderMap) -> Response {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = StatusCode::NOT_MODIFIED;
+    for name in
+        [header::CACHE_CONTROL, header::ETAG, header::EXPIRES, header::LAST_MODIFIED, header::VARY]
+    {
+        if let Some(value) = headers.get(&name) {
+            response.headers_mut().insert(name, value.clone());
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn matches(if_none_match: &'static str, etag: &'static str) -> bool {
+        etag_matches(&HeaderValue::from_static(if_none_match), &HeaderValue::from_static(etag))
+    }
+
+    fn parse(value: &'static str) -> Option<DateTime<Utc>> {
+        parse_http_date(&HeaderValue::from_static(value))
+    }
+
+    #[test]
+    fn etag_matches_weakly() {
+        assert!(matches("\"abc\"", "\"abc\""));
+        assert!(matches("W/\"abc\"", "\"abc\""));
+        assert!(matches("\"abc\"", "W/\"abc\""));
+        assert!(matches("W/\"abc\"", "W/\"abc\""));
+        assert!(!matches("\"abd\"", "W/\"abc\""));
+        assert!(!matches("\"abc", "\"abc\""));
+    }
+
+    #[test]
+    fn etag_matches_any_in_list() {
+        assert!(matches("\"x\", W/\"abc\" ,\"y\"", "W/\"abc\""));
+        assert!(!matches("\"x\", \"y\"", "W/\"abc\""));
+        assert!(matches("*", "W/\"abc\""));
+    }
+
+    #[test]
+    fn parses_http_dates() {
+        let expected = Utc.with_ymd_and_hms(1994, 11, 6, 8, 49, 37).single();
+        assert_eq!(parse("Sun, 06 Nov 1994 08:49:37 GMT"), expected);
+        assert_eq!(parse("Sunday, 06-Nov-94 08:49:37 GMT"), expected);
+        assert_eq!(parse("Sun Nov  6 08:49:37 1994"), expected);
+        assert_eq!(parse("Sun, 06 Nov 1994 08:49:37 GMT "), expected);
+    }
+
+    #[test]
+    fn rejects_invalid_http_dates() {
+        assert_eq!(parse("yesterday"), None);
+        assert_eq!(parse("Sun, 06 Nov 1994"), None);
+        // Wrong weekday
+        assert_eq!(parse("Mon, 06 Nov 1994 08:49:37 GMT"), None);
+    }
+
+    #[test]
+    fn formats_last_modified_as_http_date() {
+        let date = Utc.with_ymd_and_hms(2024, 2, 29, 23, 5, 0).unwrap();
+        let formatted = date.format(HTTP_DATE_FORMAT).to_string();
+        assert_eq!(formatted, "Thu, 29 Feb 2024 23:05:00 GMT");
+        assert_eq!(parse_http_date(&HeaderValue::from_str(&formatted).unwrap()), Some(date));
+    }
+
+    #[test]
+    fn immutable_etag_ignores_query_order() {
+        let json = [mime::APPLICATION_JSON];
+        let etag = |uri: &'static str, acceptable: &[Mime]| {
+            immutable_etag(&Uri::from_static(uri), acceptable)
+        };
+        let a = etag("http://localhost/a/b/v/sha?mode=units&limit=5", &json);
+        assert_eq!(a, etag("http://localhost/a/b/v/sha?limit=5&mode=units", &json));
+        assert_ne!(a, etag("http://localhost/a/b/v/sha?limit=6&mode=units", &json));
+        assert_ne!(a, etag("http://localhost/a/b/v/sha?limit=5&mode=units", &[mime::IMAGE_SVG]));
+    }
+
+    #[test]
+    fn checks_immutable_etag_before_rendering() {
+        let hash = immutable_etag(&Uri::from_static("http://localhost/a"), &[]);
+        let policy = CachePolicy::Immutable(hash);
+        let mut cache_headers = HeaderMap::new();
+        insert_cache_headers(&mut cache_headers, policy, &hash).unwrap();
+        let etag = cache_headers[header::ETAG].clone();
+        assert!(etag.as_bytes().starts_with(b"W/\""));
+
+        let mut headers = HeaderMap::new();
+        assert!(check_immutable(&headers, policy).unwrap().is_none());
+        headers.insert(header::IF_NONE_MATCH, etag.clone());
+        let response = check_immutable(&headers, policy).unwrap().unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::ETAG], etag);
+        assert_eq!(response.headers()[header::CACHE_CONTROL], PINNED);
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_static("*"));
+        assert!(check_immutable(&headers, policy).unwrap().is_none());
+        // Latest responses are only checked after rendering
+        let latest = CachePolicy::Latest(Utc::now());
+        headers.insert(header::IF_NONE_MATCH, etag);
+        assert!(check_immutable(&headers, latest).unwrap().is_none());
+    }
+
+    #[test]
+    fn build_hash_covers_template_contents() {
+        let dir = std::env::temp_dir().join(format!("cache-test-{}", std::process::id()));
+        fs::create_dir_all(dir.join("fragments")).unwrap();
+        fs::write(dir.join("a.html"), "a").unwrap();
+        fs::write(dir.join("fragments/b.html"), "b").unwrap();
+        let hash = || {
+            let mut hasher = blake3::Hasher::new();
+            hash_dir(&mut hasher, &dir).unwrap();
+            hasher.finalize()
+        };
+        let before = hash();
+        assert_eq!(hash(), before);
+        fs::write(dir.join("fragments/b.html"), "c").unwrap();
+        let after = hash();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_ne!(after, before);
+    }
+}
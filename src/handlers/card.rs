@@ -0,0 +1,169 @@
+use anyhow::Result;
+use image::ImageFormat;
+use objdiff_core::bindings::report::Measures;
+use serde::Serialize;
+
+use crate::{
+    blocking::Task, handlers::report::ReportTemplateUnit, models::Commit, svg, templates::render,
+    util::join_normalized, AppState,
+};
+
+pub const CARD_WIDTH: u32 = 1200;
+pub const CARD_HEIGHT: u32 = 630;
+const ICON_SIZE: f32 = 60.0;
+const TITLE_FONT_SIZE: f32 = 48.0;
+/// Average glyph width relative to the font size, used to truncate the title.
+const TITLE_CHAR_WIDTH: f32 = 0.65;
+const MARGIN: f32 = 60.0;
+const TREEMAP: Area = Area { x: 560.0, y: 190.0, w: 580.0, h: 290.0 };
+const SPARKLINE: Area = Area { x: 560.0, y: 500.0, w: 580.0, h: 80.0 };
+
+#[derive(Serialize, Copy, Clone)]
+struct Area {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+}
+
+/// Contents of a social preview card.
+pub struct Card<'a> {
+    pub title: &'a str,
+    pub subtitle: &'a str,
+    pub platform: Option<&'a str>,
+    pub measures: &'a Measures,
+    pub units: &'a [ReportTemplateUnit<'a>],
+    /// Measures at each commit up to the card's commit, oldest first
+    pub history: &'a [(Commit, Measures)],
+}
+
+#[derive(Serialize)]
+struct CardTemplateContext<'a> {
+    w: u32,
+    h: u32,
+    margin: f32,
+    icon: Option<String>,
+    title: String,
+    title_x: f32,
+    title_font_size: f32,
+    subtitle: &'a str,
+    stats: Vec<CardStat>,
+    units: &'a [ReportTemplateUnit<'a>],
+    treemap: Area,
+    sparkline: Area,
+    sparkline_line: String,
+    sparkline_area: String,
+}
+
+#[derive(Serialize)]
+struct CardStat {
+    name: &'static str,
+    value: String,
+}
+
+pub fn render_svg(card: &Card, state: &AppState) -> Result<String> {
+    let icon = card.platform.and_then(platform_icon);
+    let title_x = if icon.is_some() { MARGIN + ICON_SIZE + 20.0 } else { MARGIN };
+    let max_chars =
+        ((CARD_WIDTH as f32 - title_x - MARGIN) / (TITLE_FONT_SIZE * TITLE_CHAR_WIDTH)) as usize;
+    let title = if card.title.chars().count() > max_chars {
+        let mut title = card.title.chars().take(max_chars.saturating_sub(1)).collect::<String>();
+        title.push('…');
+        title
+    } else {
+        card.title.to_string()
+    };
+    let stats = [
+        ("Code", card.measures.matched_code_percent),
+        ("Data", card.measures.matched_data_percent),
+        ("Functions", card.measures.matched_functions_percent),
+    ]
+    .into_iter()
+    .map(|(name, percent)| CardStat { name, value: format!("{:.2}%", percent) })
+    .collect();
+    let points = sparkline_points(card.history);
+    let sparkline_line = format_points(&points);
+    let sparkline_area = if points.is_empty() {
+        String::new()
+    } else {
+        let bottom = SPARKLINE.y + SPARKLINE.h;
+        format_points(
+            &[(points[0].0, bottom)]
+                .into_iter()
+                .chain(points.iter().copied())
+                .chain([(points[points.len() - 1].0, bottom)])
+                .collect::<Vec<_>>(),
+        )
+    };
+    render(&state.templates, "card.svg", CardTemplateContext {
+        w: CARD_WIDTH,
+        h: CARD_HEIGHT,
+        margin: MARGIN,
+        icon,
+        title,
+        title_x,
+        title_font_size: TITLE_FONT_SIZE,
+        subtitle: card.subtitle,
+        stats,
+        units: card.units,
+        treemap: TREEMAP,
+        sparkline: SPARKLINE,
+        sparkline_line,
+        sparkline_area,
+    })
+}
+
+pub async fn render_image(
+    card: &Card<'_>,
+    state: &AppState,
+    format: ImageFormat,
+) -> Result<Vec<u8>> {
+    let svg = render_svg(card, state)?;
+    state.blocking.run(Task::Render, move || svg::render_image(&svg, format)).await
+}
+
+/// Loads a platform icon from `assets/platforms`, positioned as a nested SVG element.
+fn platform_icon(platform: &str) -> Option<String> {
+    let path = join_normalized("assets/platforms", format!("{}.svg", platform));
+    let icon = std::fs::read_to_string(path).ok()?;
+    // The icons are filled with currentColor
+    Some(icon.replacen(
+        "<svg ",
+        &format!(
+            r##"<svg x="{}" y="{}" width="{}" height="{}" color="#ffffff" "##,
+            MARGIN,
+            MARGIN - 10.0,
+            ICON_SIZE,
+            ICON_SIZE
+        ),
+        1,
+    ))
+}
+
+/// Plots matched code over time, scaled to the sparkline area.
+fn sparkline_points(history: &[(Commit, Measures)]) -> Vec<(f32, f32)> {
+    let (Some((first, _)), Some((last, _))) = (history.first(), history.last()) else {
+        return vec![];
+    };
+    let span = (last.timestamp - first.timestamp).num_seconds() as f32;
+    history
+        .iter()
+        .enumerate()
+        .map(|(i, (commit, measures))| {
+            let t = if span > 0.0 {
+                (commit.timestamp - first.timestamp).num_seconds() as f32 / span
+            } else if history.len() > 1 {
+                i as f32 / (history.len() - 1) as f32
+            } else {
+                1.0
+            };
+            let x = SPARKLINE.x + t * SPARKLINE.w;
+            let y = SPARKLINE.y + SPARKLINE.h * (1.0 - measures.matched_code_percent / 100.0);
+            (x, y)
+        })
+        .collect()
+}
+
+fn format_points(points: &[(f32, f32)]) -> String {
+    points.iter().map(|(x, y)| format!("{:.1},{:.1}", x, y)).collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,215 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    time::Instant,
+};
+
+use anyhow::Context;
+use axum::{
+    http::{StatusCode, Uri},
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use mime::Mime;
+use objdiff_core::bindings::report::{Measures, ReportUnit};
+use serde::Serialize;
+use url::Url;
+
+use super::AppError;
+use crate::{models::ProjectInfo, templates::render, util::UrlExt, AppState};
+
+const DEFAULT_LIMIT: u32 = 100;
+const MAX_LIMIT: u32 = 1000;
+
+#[derive(Serialize)]
+struct Comparison<'a> {
+    commit: &'a str,
+    category: Option<&'a str>,
+    versions: Vec<VersionSummary<'a>>,
+    /// Total number of units missing from at least one version
+    total_exclusive_units: usize,
+    /// Units missing from at least one version, by name
+    exclusive_units: Vec<ExclusiveUnit<'a>>,
+    /// Total number of units whose fuzzy match differs between versions
+    total_divergent_units: usize,
+    /// Units whose fuzzy match differs between versions, largest difference first
+    divergent_units: Vec<DivergentUnit<'a>>,
+}
+
+#[derive(Serialize)]
+struct VersionSummary<'a> {
+    version: &'a str,
+    measures: Measures,
+    path: String,
+}
+
+#[derive(Serialize)]
+struct ExclusiveUnit<'a> {
+    name: &'a str,
+    /// Whether the unit is present in each version, in the same order as `versions`
+    present: Vec<bool>,
+}
+
+#[derive(Serialize)]
+struct DivergentUnit<'a> {
+    name: &'a str,
+    /// Fuzzy match in each version, in the same order as `versions`
+    fuzzy_match_percent: Vec<f32>,
+    spread: f32,
+    path: String,
+}
+
+#[derive(Serialize)]
+struct CompareTemplateContext<'a> {
+    project_short_name: &'a str,
+    project_path: &'a str,
+    version: &'a str,
+    report_path: &'a str,
+    comparison: &'a Comparison<'a>,
+}
+
+/// Compares the reports of every version of a project at the same commit.
+#[allow(clippy::too_many_arguments)]
+pub async fn mode_compare(
+    state: &AppState,
+    project_info: &ProjectInfo,
+    current_version: &str,
+    category: Option<&str>,
+    limit: Option<u32>,
+    uri: Uri,
+    start: Instant,
+    acceptable: &[Mime],
+) -> Result<Response, AppError> {
+    let Some(commit) = project_info.commit.as_ref() else {
+        return Err(AppError::Status(StatusCode::NOT_FOUND));
+    };
+    let project = &project_info.project;
+    let mut reports = state.db.get_commit_versions(project.id, &commit.sha, category).await?;
+    reports.retain(|r| project_info.report_versions.contains(&r.version));
+    reports.sort_by_key(|r| project_info.report_versions.iter().position(|v| *v == r.version));
+    if reports.is_empty() {
+        return Err(AppError::Status(StatusCode::NOT_FOUND));
+    }
+    // Category measures are only stored for the report's own categories
+    if category.is_some() && reports.iter().all(|r| r.measures.is_none()) {
+        return Err(AppError::Status(StatusCode::NOT_FOUND));
+    }
+    let request_url = Url::parse(&uri.to_string()).context("Failed to parse URI")?;
+    let version_url = |version: &str| {
+        request_url
+            .with_path(&format!("/{}/{}/{}/{}", project.owner, project.repo, version, commit.sha))
+            .query_param("mode", None)
+            .query_param("limit", None)
+    };
+    let versions = reports
+        .iter()
+        .map(|report| VersionSummary {
+            version: &report.version,
+            measures: report.measures.unwrap_or_default(),
+            path: version_url(&report.version).path_and_query().to_string(),
+        })
+        .collect::<Vec<_>>();
+
+    // Each unit's ID in each version
+    let mut units = BTreeMap::<&str, Vec<Option<&[u8]>>>::new();
+    for (i, report) in reports.iter().enumerate() {
+        for (name, id) in &report.units {
+            units.entry(name).or_insert_with(|| vec![None; reports.len()])[i] = Some(id);
+        }
+    }
+    // Units are content-addressed, so a unit with the same ID in every version is neither
+    // exclusive nor divergent. Only load the rest, and only the ones that could diverge
+    // unless the category filter needs them.
+    units.retain(|_, ids| ids.iter().any(|id| *id != ids[0]));
+    let ids = units
+        .values()
+        .filter(|ids| category.is_some() || ids.iter().all(Option::is_some))
+        .flatten()
+        .flatten()
+        .map(|id| id.to_vec())
+        .collect::<HashSet<_>>();
+    let loaded = state.db.get_report_units_by_id(ids).await?;
+    let in_category = |unit: &ReportUnit| {
+        category.is_none_or(|id| {
+            unit.metadata.as_ref().is_some_and(|m| m.progress_categories.iter().any(|c| c == id))
+        })
+    };
+    let mut exclusive_units = Vec::new();
+    let mut divergent_units = Vec::new();
+    let current_url = version_url(current_version);
+    for (name, ids) in units {
+        // The unit's ID in each version where it's present and in the category
+        let present = ids
+            .into_iter()
+            .map(|id| {
+                id.filter(|id| {
+                    category.is_none() || loaded.get(*id).is_some_and(|u| in_category(u))
+                })
+            })
+            .collect::<Vec<_>>();
+        if present.iter().all(Option::is_none) {
+            continue;
+        }
+        if present.iter().any(Option::is_none) {
+            exclusive_units.push(ExclusiveUnit {
+                name,
+                present: present.iter().map(Option::is_some).collect(),
+            });
+            continue;
+        }
+        let fuzzy_match_percent = present
+            .iter()
+            .map(|id| {
+                id.and_then(|id| loaded.get(id))
+                    .and_then(|u| u.measures)
+                    .map_or(0.0, |m| m.fuzzy_match_percent)
+            })
+            .collect::<Vec<_>>();
+        let min = fuzzy_match_percent.iter().copied().fold(f32::INFINITY, f32::min);
+        let max = fuzzy_match_percent.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        if max > min {
+            divergent_units.push(DivergentUnit {
+                name,
+                fuzzy_match_percent,
+                spread: max - min,
+                path: current_url.query_param("unit", Some(name)).path_and_query().to_string(),
+            });
+        }
+    }
+    divergent_units
+        .sort_by(|a, b| b.spread.partial_cmp(&a.spread).unwrap_or(std::cmp::Ordering::Equal));
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT) as usize;
+    let total_exclusive_units = exclusive_units.len();
+    let total_divergent_units = divergent_units.len();
+    exclusive_units.truncate(limit);
+    divergent_units.truncate(limit);
+    let comparison = Comparison {
+        commit: &commit.sha,
+        category,
+        versions,
+        total_exclusive_units,
+        exclusive_units,
+        total_divergent_units,
+        divergent_units,
+    };
+
+    for mime in acceptable {
+        if (mime.type_() == mime::STAR && mime.subtype() == mime::STAR)
+            || (mime.type_() == mime::TEXT && mime.subtype() == mime::HTML)
+        {
+            let project_path = format!("/{}/{}", project.owner, project.repo);
+            let mut rendered = render(&state.templates, "compare.html", CompareTemplateContext {
+                project_short_name: project.short_name(),
+                project_path: &project_path,
+                version: current_version,
+                report_path: current_url.path_and_query(),
+                comparison: &comparison,
+            })?;
+            let elapsed = start.elapsed();
+            rendered = rendered.replace("[[time]]", &format!("{}ms", elapsed.as_millis()));
+            return Ok(Html(rendered).into_response());
+        } else if mime.type_() == mime::APPLICATION && mime.subtype() == mime::JSON {
+            return Ok(Json(comparison).into_response());
+        }
+    }
+    Err(AppError::Status(StatusCode::NOT_ACCEPTABLE))
+}
@@ -0,0 +1,177 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Instant,
+};
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use objdiff_core::bindings::report::Measures;
+use serde::Serialize;
+
+use super::{parse_accept, AppError};
+use crate::{models::ProjectSummary, templates::render, AppState};
+
+/// Number of projects in each leaderboard.
+const LEADERBOARD_SIZE: usize = 10;
+
+#[derive(Serialize)]
+struct Dashboard {
+    /// Totals across all projects
+    total: AggregateStats,
+    /// Totals per platform, by platform ID
+    platforms: BTreeMap<String, AggregateStats>,
+    /// Projects with the most reports in the last month
+    most_active: Vec<ProjectStats>,
+    /// Projects with the most code matched in the last month
+    top_gains: Vec<ProjectStats>,
+}
+
+#[derive(Serialize, Default)]
+struct AggregateStats {
+    projects: usize,
+    measures: Measures,
+    /// Byte counts, since `Measures` serializes them as strings
+    bytes: Bytes,
+    week: Gains,
+    month: Gains,
+}
+
+#[derive(Serialize, Default)]
+struct Bytes {
+    total_code: u64,
+    matched_code: u64,
+    total_data: u64,
+    matched_data: u64,
+}
+
+impl From<&Measures> for Bytes {
+    fn from(measures: &Measures) -> Self {
+        Self {
+            total_code: measures.total_code,
+            matched_code: measures.matched_code,
+            total_data: measures.total_data,
+            matched_data: measures.matched_data,
+        }
+    }
+}
+
+/// Change in matched bytes and functions over a period.
+#[derive(Serialize, Default, Copy, Clone)]
+struct Gains {
+    matched_code: i64,
+    matched_data: i64,
+    matched_functions: i64,
+}
+
+impl Gains {
+    fn between(from: &Measures, to: &Measures) -> Self {
+        Self {
+            matched_code: to.matched_code as i64 - from.matched_code as i64,
+            matched_data: to.matched_data as i64 - from.matched_data as i64,
+            matched_functions: to.matched_functions as i64 - from.matched_functions as i64,
+        }
+    }
+
+    fn add(&mut self, other: Gains) {
+        self.matched_code += other.matched_code;
+        self.matched_data += other.matched_data;
+        self.matched_functions += other.matched_functions;
+    }
+}
+
+#[derive(Serialize, Clone)]
+struct ProjectStats {
+    path: String,
+    name: String,
+    platform: Option<String>,
+    version: String,
+    timestamp: DateTime<Utc>,
+    measures: Measures,
+    /// Number of reports in the last month
+    reports: usize,
+    week: Gains,
+    month: Gains,
+}
+
+pub async fn get_dashboard(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> Result<Response, AppError> {
+    let start = Instant::now();
+    let acceptable = parse_accept(&headers, None);
+    if acceptable.is_empty() {
+        return Err(AppError::Status(StatusCode::NOT_ACCEPTABLE));
+    }
+    let now = Utc::now();
+    let week_ago = state.db.get_measures_at(now - TimeDelta::weeks(1)).await?;
+    let month_ago = state.db.get_measures_at(now - TimeDelta::days(30)).await?;
+    let report_counts = state.db.get_report_counts(now - TimeDelta::days(30)).await?;
+    let mut projects = Vec::new();
+    for ProjectSummary { info, measures } in state.db.get_projects().await? {
+        let (Some(commit), Some(version)) = (info.commit.as_ref(), info.default_version()) else {
+            continue;
+        };
+        let key = (info.project.id, version.to_string());
+        let baseline =
+            |history: &HashMap<_, Measures>| history.get(&key).copied().unwrap_or_default();
+        projects.push(ProjectStats {
+            path: format!("/{}/{}", info.project.owner, info.project.repo),
+            name: info.project.name().into_owned(),
+            platform: info.project.platform.clone(),
+            version: version.to_string(),
+            timestamp: commit.timestamp,
+            measures,
+            reports: report_counts.get(&key).copied().unwrap_or_default(),
+            week: Gains::between(&baseline(&week_ago), &measures),
+            month: Gains::between(&baseline(&month_ago), &measures),
+        });
+    }
+
+    let mut total = AggregateStats::default();
+    let mut platforms = BTreeMap::<String, AggregateStats>::new();
+    for project in &projects {
+        let platform = project.platform.clone().unwrap_or_else(|| "unknown".to_string());
+        for stats in [&mut total, platforms.entry(platform).or_default()] {
+            stats.projects += 1;
+            stats.week.add(project.week);
+            stats.month.add(project.month);
+        }
+    }
+    total.measures = projects.iter().map(|p| p.measures).collect();
+    total.bytes = Bytes::from(&total.measures);
+    for (platform, stats) in &mut platforms {
+        stats.measures = projects
+            .iter()
+            .filter(|p| p.platform.as_deref().unwrap_or("unknown") == platform)
+            .map(|p| p.measures)
+            .collect();
+        stats.bytes = Bytes::from(&stats.measures);
+    }
+    let mut most_active = projects.iter().filter(|p| p.reports > 0).cloned().collect::<Vec<_>>();
+    most_active.sort_by_key(|p| std::cmp::Reverse(p.reports));
+    most_active.truncate(LEADERBOARD_SIZE);
+    let mut top_gains =
+        projects.into_iter().filter(|p| p.month.matched_code > 0).collect::<Vec<_>>();
+    top_gains.sort_by_key(|p| std::cmp::Reverse(p.month.matched_code));
+    top_gains.truncate(LEADERBOARD_SIZE);
+    let dashboard = Dashboard { total, platforms, most_active, top_gains };
+
+    for mime in acceptable {
+        if (mime.type_() == mime::STAR && mime.subtype() == mime::STAR)
+            || (mime.type_() == mime::TEXT && mime.subtype() == mime::HTML)
+        {
+            let mut rendered = render(&state.templates, "dashboard.html", &dashboard)?;
+            let elapsed = start.elapsed();
+            rendered = rendered.replace("[[time]]", &format!("{}ms", elapsed.as_millis()));
+            return Ok(Html(rendered).into_response());
+        } else if mime.type_() == mime::APPLICATION && mime.subtype() == mime::JSON {
+            return Ok(Json(dashboard).into_response());
+        }
+    }
+    Err(AppError::Status(StatusCode::NOT_ACCEPTABLE))
+}
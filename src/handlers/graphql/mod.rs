@@ -0,0 +1,827 @@
+//! A read-only GraphQL endpoint over projects and reports. Only the query subset handled by
+//! [`parser`] is supported, and introspection isn't available: the schema is served as SDL
+//! from `/api/v1/graphql/schema.graphql` instead.
+
+mod parser;
+
+use anyhow::{anyhow, bail, Result};
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use objdiff_core::bindings::report::{Measures, ReportItem, ReportUnit};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use self::parser::{Field, Operation};
+use super::AppError;
+use crate::{
+    models::{Commit, ProjectSummary, ReportFile},
+    AppState,
+};
+
+/// Maximum estimated cost of a query. See [`complexity`].
+const MAX_COMPLEXITY: u64 = 50_000;
+/// Cost of loading a report, on top of the cost of its fields.
+const REPORT_COST: u64 = 1_000;
+/// Number of list items returned when `first` isn't given.
+const DEFAULT_FIRST: u64 = 100;
+const MAX_FIRST: u64 = 5_000;
+/// Pagination arguments of list fields, with [`DEFAULT_FIRST`] as the default.
+const FIRST_ARG: &str = "first: Int = 100";
+const OFFSET_ARG: &str = "offset: Int = 0";
+const FUZZY_RANGE: &str = "minFuzzy is inclusive and maxFuzzy is exclusive";
+
+/// A field in the schema, used to generate the SDL, validate queries and estimate their cost.
+struct FieldDef {
+    name: &'static str,
+    /// Type in SDL syntax, like `[Unit!]!`
+    ty: &'static str,
+    /// Arguments in SDL syntax, like `name: String!`
+    args: &'static [&'static str],
+    description: Option<&'static str>,
+    cost: u64,
+}
+
+impl FieldDef {
+    /// Object type of the field, or None for scalars.
+    fn object_type(&self) -> Option<&'static str> {
+        let name = self.ty.trim_matches(['[', ']', '!']);
+        TYPES.iter().any(|(ty, _)| *ty == name).then_some(name)
+    }
+
+    fn is_list(&self) -> bool { self.ty.starts_with('[') }
+
+    fn has_arg(&self, name: &str) -> bool {
+        self.args.iter().any(|arg| arg.split(':').next().map(str::trim) == Some(name))
+    }
+}
+
+const fn field(name: &'static str, ty: &'static str) -> FieldDef {
+    FieldDef { name, ty, args: &[], description: None, cost: 1 }
+}
+
+const fn described(name: &'static str, ty: &'static str, description: &'static str) -> FieldDef {
+    FieldDef { name, ty, args: &[], description: Some(description), cost: 1 }
+}
+
+const MEASURES_FIELDS: &[FieldDef] = &[
+    field("fuzzyMatchPercent", "Float!"),
+    field("totalCode", "Float!"),
+    field("matchedCode", "Float!"),
+    field("matchedCodePercent", "Float!"),
+    field("totalData", "Float!"),
+    field("matchedData", "Float!"),
+    field("matchedDataPercent", "Float!"),
+    field("totalFunctions", "Int!"),
+    field("matchedFunctions", "Int!"),
+    field("matchedFunctionsPercent", "Float!"),
+    field("completeCode", "Float!"),
+    field("completeCodePercent", "Float!"),
+    field("completeData", "Float!"),
+    field("completeDataPercent", "Float!"),
+    field("totalUnits", "Int!"),
+    field("completeUnits", "Int!"),
+];
+
+const TYPES: &[(&str, &[FieldDef])] = &[
+    ("Query", &[
+        FieldDef {
+            args: &["platform: String", FIRST_ARG, OFFSET_ARG],
+            ..field("projects", "[Project!]!")
+        },
+        FieldDef { args: &["owner: String!", "repo: String!"], ..field("project", "Project") },
+    ]),
+    ("Project", &[
+        field("id", "Int!"),
+        field("owner", "String!"),
+        field("repo", "String!"),
+        field("name", "String!"),
+        field("shortName", "String!"),
+        field("platform", "String"),
+        field("defaultVersion", "String"),
+        described("versions", "[String!]!", "Versions with a report at the latest commit"),
+        described("commit", "Commit", "Latest commit"),
+        described("measures", "Measures!", "Measures of the default version at the latest commit"),
+        FieldDef {
+            args: &["version: String", "commit: String"],
+            cost: REPORT_COST,
+            ..described("report", "Report", "Defaults to the default version at the latest commit")
+        },
+    ]),
+    ("Commit", &[field("sha", "String!"), field("timestamp", "String!")]),
+    ("Report", &[
+        field("version", "String!"),
+        field("commit", "Commit!"),
+        field("measures", "Measures!"),
+        field("categories", "[Category!]!"),
+        FieldDef {
+            args: &[
+                "category: String",
+                "name: String",
+                "minFuzzy: Float",
+                "maxFuzzy: Float",
+                "complete: Boolean",
+                FIRST_ARG,
+                OFFSET_ARG,
+            ],
+            ..described("units", "[Unit!]!", FUZZY_RANGE)
+        },
+        FieldDef { args: &["name: String!"], ..field("unit", "Unit") },
+    ]),
+    ("Category", &[
+        field("id", "String!"),
+        field("name", "String!"),
+        field("measures", "Measures!"),
+    ]),
+    ("Unit", &[
+        field("name", "String!"),
+        field("fuzzyMatchPercent", "Float!"),
+        field("complete", "Boolean!"),
+        field("moduleName", "String"),
+        field("sourcePath", "String"),
+        field("categories", "[String!]!"),
+        field("measures", "Measures!"),
+        FieldDef {
+            args: &["name: String", "minFuzzy: Float", "maxFuzzy: Float", FIRST_ARG, OFFSET_ARG],
+            ..described("functions", "[Function!]!", FUZZY_RANGE)
+        },
+        FieldDef { args: &[FIRST_ARG, OFFSET_ARG], ..field("sections", "[Function!]!") },
+    ]),
+    ("Function", &[
+        field("name", "String!"),
+        field("demangledName", "String"),
+        field("size", "Float!"),
+        field("fuzzyMatchPercent", "Float!"),
+        field("address", "Float"),
+    ]),
+    ("Measures", MEASURES_FIELDS),
+];
+
+/// Generates the schema in SDL from [`TYPES`].
+pub fn schema() -> String {
+    let mut out = String::new();
+    for (i, (name, fields)) in TYPES.iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        out.push_str(&format!("type {} {{\n", name));
+        for field in *fields {
+            if let Some(description) = field.description {
+                out.push_str(&format!("  \"{}\"\n", description));
+            }
+            let args = if field.args.is_empty() {
+                String::new()
+            } else {
+                format!("({})", field.args.join(", "))
+            };
+            out.push_str(&format!("  {}{}: {}\n", field.name, args, field.ty));
+        }
+        out.push_str("}\n");
+    }
+    out
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphqlRequest {
+    query: String,
+    operation_name: Option<String>,
+    #[serde(default)]
+    variables: Option<Map<String, Value>>,
+}
+
+/// Query parameters for GET requests, with `variables` as a JSON string.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphqlParams {
+    query: String,
+    operation_name: Option<String>,
+    variables: Option<String>,
+}
+
+pub async fn get_graphql(
+    State(state): State<AppState>,
+    Query(params): Query<GraphqlParams>,
+) -> Result<Response, AppError> {
+    let variables = match params.variables.as_deref() {
+        Some(variables) => Some(
+            serde_json::from_str(variables)
+                .map_err(|_| AppError::Status(StatusCode::BAD_REQUEST))?,
+        ),
+        None => None,
+    };
+    Ok(execute(&state, GraphqlRequest {
+        query: params.query,
+        operation_name: params.operation_name,
+        variables,
+    })
+    .await)
+}
+
+pub async fn post_graphql(
+    State(state): State<AppState>,
+    Json(request): Json<GraphqlRequest>,
+) -> Result<Response, AppError> {
+    Ok(execute(&state, request).await)
+}
+
+pub async fn get_schema() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], schema())
+}
+
+async fn execute(state: &AppState, request: GraphqlRequest) -> Response {
+    let result = async {
+        let operation = parser::parse(&request.query, request.operation_name.as_deref())?;
+        let variables = bind_variables(&operation, request.variables.unwrap_or_default())?;
+        let cost = complexity(&operation.selection, "Query", &variables)?;
+        if cost > MAX_COMPLEXITY {
+            bail!("Query is too complex: estimated cost {} exceeds {}", cost, MAX_COMPLEXITY);
+        }
+        Executor { state, variables: &variables }.query(&operation.selection).await
+    }
+    .await;
+    match result {
+        Ok(data) => Json(json!({ "data": data })).into_response(),
+        Err(e) => Json(json!({ "data": null, "errors": [{ "message": format!("{:#}", e) }] }))
+            .into_response(),
+    }
+}
+
+/// Applies variable defaults. Variables not defined by the operation are ignored.
+fn bind_variables(
+    operation: &Operation,
+    mut provided: Map<String, Value>,
+) -> Result<Map<String, Value>> {
+    let mut variables = Map::new();
+    for definition in &operation.variables {
+        let value = match (provided.remove(&definition.name), &definition.default) {
+            (Some(value), _) => value,
+            (None, Some(default)) => to_json(default, &Map::new())?,
+            (None, None) => Value::Null,
+        };
+        variables.insert(definition.name.clone(), value);
+    }
+    Ok(variables)
+}
+
+fn to_json(value: &parser::Value, variables: &Map<String, Value>) -> Result<Value> {
+    to_json_nested(value, variables, 0)
+}
+
+/// Parsed values are already limited to [`parser::MAX_DEPTH`], but check again rather than
+/// rely on it.
+fn to_json_nested(
+    value: &parser::Value,
+    variables: &Map<String, Value>,
+    depth: usize,
+) -> Result<Value> {
+    use parser::Value as V;
+    if depth >= parser::MAX_DEPTH {
+        bail!("Value is nested too deeply");
+    }
+    Ok(match value {
+        V::Variable(name) => {
+            variables.get(name).cloned().ok_or_else(|| anyhow!("Undefined variable ${}", name))?
+        }
+        V::Int(i) => json!(i),
+        V::Float(f) => json!(f),
+        V::String(s) | V::Enum(s) => json!(s),
+        V::Boolean(b) => json!(b),
+        V::Null => Value::Null,
+        V::List(items) => {
+            let items = items.iter().map(|v| to_json_nested(v, variables, depth + 1));
+            Value::Array(items.collect::<Result<_>>()?)
+        }
+        V::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(k, v)| Ok((k.clone(), to_json_nested(v, variables, depth + 1)?)))
+                .collect::<Result<_>>()?,
+        ),
+    })
+}
+
+/// Fields are validated before execution, so resolvers only hit this if the schema and the
+/// resolvers disagree.
+fn unknown_field(field: &Field, ty: &str) -> anyhow::Error {
+    anyhow!("Unknown field {} on type {}", field.name, ty)
+}
+
+fn field_def(ty: &str, name: &str) -> Result<&'static FieldDef> {
+    TYPES
+        .iter()
+        .find(|(t, _)| *t == ty)
+        .and_then(|(_, fields)| fields.iter().find(|f| f.name == name))
+        .ok_or_else(|| anyhow!("Unknown field {} on type {}", name, ty))
+}
+
+/// Validates a selection against the schema and estimates its cost: each field costs one
+/// (or [`REPORT_COST`] for reports), and the cost of a list's items is multiplied by the
+/// number of items requested.
+fn complexity(selection: &[Field], ty: &str, variables: &Map<String, Value>) -> Result<u64> {
+    let mut total = 0u64;
+    for field in selection {
+        if field.name == "__typename" {
+            continue;
+        }
+        let def = field_def(ty, &field.name)?;
+        for (name, _) in &field.arguments {
+            if !def.has_arg(name) {
+                bail!("Unknown argument {} on field {}.{}", name, ty, field.name);
+            }
+        }
+        let children = match def.object_type() {
+            Some(_) if field.selection.is_empty() => {
+                bail!("Field {}.{} must have a selection", ty, field.name)
+            }
+            Some(child) => complexity(&field.selection, child, variables)?,
+            None if !field.selection.is_empty() => {
+                bail!("Field {}.{} can't have a selection", ty, field.name)
+            }
+            None => 0,
+        };
+        let multiplier = if def.is_list() { first(field, variables)? } else { 1 };
+        total = total.saturating_add(def.cost.saturating_add(multiplier.saturating_mul(children)));
+    }
+    Ok(total)
+}
+
+fn argument(field: &Field, name: &str, variables: &Map<String, Value>) -> Result<Value> {
+    match field.arguments.iter().find(|(n, _)| n == name) {
+        Some((_, value)) => to_json(value, variables),
+        None => Ok(Value::Null),
+    }
+}
+
+fn string_arg(field: &Field, name: &str, variables: &Map<String, Value>) -> Result<Option<String>> {
+    match argument(field, name, variables)? {
+        Value::Null => Ok(None),
+        Value::String(s) => Ok(Some(s)),
+        _ => bail!("Argument {} must be a string", name),
+    }
+}
+
+fn float_arg(field: &Field, name: &str, variables: &Map<String, Value>) -> Result<Option<f32>> {
+    match argument(field, name, variables)? {
+        Value::Null => Ok(None),
+        Value::Number(n) => Ok(n.as_f64().map(|n| n as f32)),
+        _ => bail!("Argument {} must be a number", name),
+    }
+}
+
+fn bool_arg(field: &Field, name: &str, variables: &Map<String, Value>) -> Result<Option<bool>> {
+    match argument(field, name, variables)? {
+        Value::Null => Ok(None),
+        Value::Bool(b) => Ok(Some(b)),
+        _ => bail!("Argument {} must be a boolean", name),
+    }
+}
+
+fn int_arg(field: &Field, name: &str, variables: &Map<String, Value>) -> Result<Option<u64>> {
+    match argument(field, name, variables)? {
+        Value::Null => Ok(None),
+        Value::Number(n) => n
+            .as_u64()
+            .map(Some)
+            .ok_or_else(|| anyhow!("Argument {} must be a non-negative integer", name)),
+        _ => bail!("Argument {} must be an integer", name),
+    }
+}
+
+fn first(field: &Field, variables: &Map<String, Value>) -> Result<u64> {
+    let first = int_arg(field, "first", variables)?.unwrap_or(DEFAULT_FIRST);
+    if first > MAX_FIRST {
+        bail!("Argument first must be at most {}", MAX_FIRST);
+    }
+    Ok(first)
+}
+
+/// Applies `first` and `offset` to a list field.
+fn paginate<T>(
+    items: impl Iterator<Item = T>,
+    field: &Field,
+    variables: &Map<String, Value>,
+) -> Result<Vec<T>> {
+    let offset = int_arg(field, "offset", variables)?.unwrap_or(0);
+    Ok(items.skip(offset as usize).take(first(field, variables)? as usize).collect())
+}
+
+fn fuzzy_in_range(
+    fuzzy_match_percent: f32,
+    field: &Field,
+    variables: &Map<String, Value>,
+) -> Result<bool> {
+    let min = float_arg(field, "minFuzzy", variables)?;
+    let max = float_arg(field, "maxFuzzy", variables)?;
+    Ok(min.is_none_or(|min| fuzzy_match_percent >= min)
+        && max.is_none_or(|max| fuzzy_match_percent < max))
+}
+
+struct Executor<'a> {
+    state: &'a AppState,
+    variables: &'a Map<String, Value>,
+}
+
+impl Executor<'_> {
+    async fn query(&self, selection: &[Field]) -> Result<Value> {
+        let mut out = Map::new();
+        let mut projects = None;
+        for field in selection {
+            let value = match field.name.as_str() {
+                "__typename" => json!("Query"),
+                "projects" | "project" => {
+                    if projects.is_none() {
+                        projects = Some(self.state.db.get_projects().await?);
+                    }
+                    let projects = projects.as_deref().unwrap_or_default();
+                    if field.name == "projects" {
+                        let platform = string_arg(field, "platform", self.variables)?;
+                        let mut items = Vec::new();
+                        for project in paginate(
+                            projects.iter().filter(|p| {
+                                platform.is_none()
+                                    || p.info.project.platform.as_deref() == platform.as_deref()
+                            }),
+                            field,
+                            self.variables,
+                        )? {
+                            items.push(self.project(project, &field.selection).await?);
+                        }
+                        Value::Array(items)
+                    } else {
+                        let owner = string_arg(field, "owner", self.variables)?
+                            .ok_or_else(|| anyhow!("Argument owner is required"))?;
+                        let repo = string_arg(field, "repo", self.variables)?
+                            .ok_or_else(|| anyhow!("Argument repo is required"))?;
+                        match projects.iter().find(|p| {
+                            p.info.project.owner.eq_ignore_ascii_case(&owner)
+                                && p.info.project.repo.eq_ignore_ascii_case(&repo)
+                        }) {
+                            Some(project) => self.project(project, &field.selection).await?,
+                            None => Value::Null,
+                        }
+                    }
+                }
+                _ => return Err(unknown_field(field, "Query")),
+            };
+            out.insert(field.response_key().to_string(), value);
+        }
+        Ok(Value::Object(out))
+    }
+
+    async fn project(&self, summary: &ProjectSummary, selection: &[Field]) -> Result<Value> {
+        let ProjectSummary { info, measures } = summary;
+        let project = &info.project;
+        let mut out = Map::new();
+        for field in selection {
+            let value = match field.name.as_str() {
+                "__typename" => json!("Project"),
+                "id" => json!(project.id),
+                "owner" => json!(project.owner),
+                "repo" => json!(project.repo),
+                "name" => json!(project.name()),
+                "shortName" => json!(project.short_name()),
+                "platform" => json!(project.platform),
+                "defaultVersion" => json!(info.default_version()),
+                "versions" => json!(info.report_versions),
+                "commit" => match &info.commit {
+                    Some(commit) => resolve_commit(commit, &field.selection)?,
+                    None => Value::Null,
+                },
+                "measures" => resolve_measures(measures, &field.selection)?,
+                "report" => {
+                    let version = match string_arg(field, "version", self.variables)? {
+                        Some(version) if !version.eq_ignore_ascii_case("default") => Some(version),
+                        _ => info.default_version().map(str::to_string),
+                    };
+                    let commit = match string_arg(field, "commit", self.variables)? {
+                        Some(commit) if !commit.eq_ignore_ascii_case("latest") => Some(commit),
+                        _ => info.commit.as_ref().map(|c| c.sha.clone()),
+                    };
+                    let report = match (version, commit) {
+                        (Some(version), Some(commit)) => {
+                            self.state
+                                .db
+                                .get_report(&project.owner, &project.repo, &commit, &version)
+                                .await?
+                        }
+                        _ => None,
+                    };
+                    match report {
+                        Some(report) => self.report(&report, &field.selection)?,
+                        None => Value::Null,
+                    }
+                }
+                _ => return Err(unknown_field(field, "Project")),
+            };
+            out.insert(field.response_key().to_string(), value);
+        }
+        Ok(Value::Object(out))
+    }
+
+    fn report(&self, file: &ReportFile, selection: &[Field]) -> Result<Value> {
+        let report = &file.report;
+        let mut out = Map::new();
+        for field in selection {
+            let value = match field.name.as_str() {
+                "__typename" => json!("Report"),
+                "version" => json!(file.version),
+                "commit" => resolve_commit(&file.commit, &field.selection)?,
+                "measures" => {
+                    resolve_measures(&report.measures.unwrap_or_default(), &field.selection)?
+                }
+                "categories" => Value::Array(
+                    report
+                        .categories
+                        .iter()
+                        .map(|category| {
+                            let mut out = Map::new();
+                            for field in &field.selection {
+                                let value = match field.name.as_str() {
+                                    "__typename" => json!("Category"),
+                                    "id" => json!(category.id),
+                                    "name" => json!(category.name),
+                                    "measures" => resolve_measures(
+                                        &category.measures.unwrap_or_default(),
+                                        &field.selection,
+                                    )?,
+                                    _ => return Err(unknown_field(field, "Category")),
+                                };
+                                out.insert(field.response_key().to_string(), value);
+                            }
+                            Ok(Value::Object(out))
+                        })
+                        .collect::<Result<_>>()?,
+                ),
+                "units" => {
+                    let category = string_arg(field, "category", self.variables)?;
+                    let name = string_arg(field, "name", self.variables)?;
+                    let complete = bool_arg(field, "complete", self.variables)?;
+                    let mut units = Vec::new();
+                    for unit in &report.units {
+                        let metadata = unit.metadata.as_ref();
+                        if category.as_ref().is_some_and(|id| {
+                            !metadata.is_some_and(|m| m.progress_categories.contains(id))
+                        }) || name
+                            .as_ref()
+                            .is_some_and(|name| !unit.name.contains(name.as_str()))
+                            || complete.is_some_and(|complete| unit_complete(unit) != complete)
+                            || !fuzzy_in_range(unit_fuzzy(unit), field, self.variables)?
+                        {
+                            continue;
+                        }
+                        units.push(unit);
+                    }
+                    Value::Array(
+                        paginate(units.into_iter(), field, self.variables)?
+                            .into_iter()
+                            .map(|unit| self.unit(unit, &field.selection))
+                            .collect::<Result<_>>()?,
+                    )
+                }
+                "unit" => {
+                    let name = string_arg(field, "name", self.variables)?
+                        .ok_or_else(|| anyhow!("Argument name is required"))?;
+                    match report.units.iter().find(|u| u.name == name) {
+                        Some(unit) => self.unit(unit, &field.selection)?,
+                        None => Value::Null,
+                    }
+                }
+                _ => return Err(unknown_field(field, "Report")),
+            };
+            out.insert(field.response_key().to_string(), value);
+        }
+        Ok(Value::Object(out))
+    }
+
+    fn unit(&self, unit: &ReportUnit, selection: &[Field]) -> Result<Value> {
+        let metadata = unit.metadata.as_ref();
+        let mut out = Map::new();
+        for field in selection {
+            let value = match field.name.as_str() {
+                "__typename" => json!("Unit"),
+                "name" => json!(unit.name),
+                "fuzzyMatchPercent" => json!(unit_fuzzy(unit)),
+                "complete" => json!(unit_complete(unit)),
+                "moduleName" => json!(metadata.and_then(|m| m.module_name.as_deref())),
+                "sourcePath" => json!(metadata.and_then(|m| m.source_path.as_deref())),
+                "categories" => {
+                    json!(metadata.map(|m| m.progress_categories.as_slice()).unwrap_or_default())
+                }
+                "measures" => {
+                    resolve_measures(&unit.measures.unwrap_or_default(), &field.selection)?
+                }
+                "functions" => {
+                    let name = string_arg(field, "name", self.variables)?;
+                    let mut functions = Vec::new();
+                    for function in &unit.functions {
+                        if name.as_ref().is_some_and(|name| {
+                            !function.name.contains(name.as_str())
+                                && !function
+                                    .metadata
+                                    .as_ref()
+                                    .and_then(|m| m.demangled_name.as_ref())
+                                    .is_some_and(|d| d.contains(name.as_str()))
+                        }) || !fuzzy_in_range(
+                            function.fuzzy_match_percent,
+                            field,
+                            self.variables,
+                        )? {
+                            continue;
+                        }
+                        functions.push(function);
+                    }
+                    Value::Array(
+                        paginate(functions.into_iter(), field, self.variables)?
+                            .into_iter()
+                            .map(|f| resolve_item(f, &field.selection))
+                            .collect::<Result<_>>()?,
+                    )
+                }
+                "sections" => Value::Array(
+                    paginate(unit.sections.iter(), field, self.variables)?
+                        .into_iter()
+                        .map(|s| resolve_item(s, &field.selection))
+                        .collect::<Result<_>>()?,
+                ),
+                _ => return Err(unknown_field(field, "Unit")),
+            };
+            out.insert(field.response_key().to_string(), value);
+        }
+        Ok(Value::Object(out))
+    }
+}
+
+fn unit_fuzzy(unit: &ReportUnit) -> f32 {
+    unit.measures.as_ref().map_or(0.0, |m| m.fuzzy_match_percent)
+}
+
+fn unit_complete(unit: &ReportUnit) -> bool {
+    unit.metadata.as_ref().and_then(|m| m.complete).unwrap_or(false)
+}
+
+fn resolve_commit(commit: &Commit, selection: &[Field]) -> Result<Value> {
+    let mut out = Map::new();
+    for field in selection {
+        let value = match field.name.as_str() {
+            "__typename" => json!("Commit"),
+            "sha" => json!(commit.sha),
+            "timestamp" => json!(commit.timestamp),
+            _ => return Err(unknown_field(field, "Commit")),
+        };
+        out.insert(field.response_key().to_string(), value);
+    }
+    Ok(Value::Object(out))
+}
+
+fn resolve_item(item: &ReportItem, selection: &[Field]) -> Result<Value> {
+    let metadata = item.metadata.as_ref();
+    let mut out = Map::new();
+    for field in selection {
+        let value = match field.name.as_str() {
+            "__typename" => json!("Function"),
+            "name" => json!(item.name),
+            "demangledName" => json!(metadata.and_then(|m| m.demangled_name.as_deref())),
+            "size" => json!(item.size),
+            "fuzzyMatchPercent" => json!(item.fuzzy_match_percent),
+            "address" => json!(metadata.and_then(|m| m.virtual_address)),
+            _ => return Err(unknown_field(field, "Function")),
+        };
+        out.insert(field.response_key().to_string(), value);
+    }
+    Ok(Value::Object(out))
+}
+
+fn resolve_measures(measures: &Measures, selection: &[Field]) -> Result<Value> {
+    let mut out = Map::new();
+    for field in selection {
+        let value = match field.name.as_str() {
+            "__typename" => json!("Measures"),
+            "fuzzyMatchPercent" => json!(measures.fuzzy_match_percent),
+            "totalCode" => json!(measures.total_code),
+            "matchedCode" => json!(measures.matched_code),
+            "matchedCodePercent" => json!(measures.matched_code_percent),
+            "totalData" => json!(measures.total_data),
+            "matchedData" => json!(measures.matched_data),
+            "matchedDataPercent" => json!(measures.matched_data_percent),
+            "totalFunctions" => json!(measures.total_functions),
+            "matchedFunctions" => json!(measures.matched_functions),
+            "matchedFunctionsPercent" => json!(measures.matched_functions_percent),
+            "completeCode" => json!(measures.complete_code),
+            "completeCodePercent" => json!(measures.complete_code_percent),
+            "completeData" => json!(measures.complete_data),
+            "completeDataPercent" => json!(measures.complete_data_percent),
+            "totalUnits" => json!(measures.total_units),
+            "completeUnits" => json!(measures.complete_units),
+            _ => return Err(unknown_field(field, "Measures")),
+        };
+        out.insert(field.response_key().to_string(), value);
+    }
+    Ok(Value::Object(out))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Operation { parser::parse(source, None).unwrap() }
+
+    fn cost(source: &str, variables: Value) -> Result<u64> {
+        let operation = parse(source);
+        let Value::Object(variables) = variables else { panic!("variables must be an object") };
+        let variables = bind_variables(&operation, variables)?;
+        complexity(&operation.selection, "Query", &variables)
+    }
+
+    #[test]
+    fn schema_types_exist() {
+        const SCALARS: &[&str] = &["Int", "Float", "String", "Boolean"];
+        for (_, fields) in TYPES {
+            for field in *fields {
+                let name = field.ty.trim_matches(['[', ']', '!']);
+                assert!(
+                    SCALARS.contains(&name) || field.object_type().is_some(),
+                    "unknown type {}",
+                    field.ty
+                );
+            }
+        }
+        let schema = schema();
+        assert!(schema.starts_with("type Query {\n"));
+        assert!(schema.contains(
+            "  projects(platform: String, first: Int = 100, offset: Int = 0): [Project!]!\n"
+        ));
+        assert!(schema.contains("  \"Latest commit\"\n  commit: Commit\n"));
+    }
+
+    #[test]
+    fn binds_variable_defaults() {
+        let operation =
+            parse("query($a: Int = 5, $b: String = \"x\", $c: Int) { projects { id } }");
+        let provided = json!({ "b": "y", "unused": 1 });
+        let Value::Object(provided) = provided else { unreachable!() };
+        let variables = bind_variables(&operation, provided).unwrap();
+        assert_eq!(Value::Object(variables), json!({ "a": 5, "b": "y", "c": null }));
+    }
+
+    #[test]
+    fn estimates_list_cost_with_first() {
+        // projects (1) + first * (id (1))
+        assert_eq!(cost("{ projects(first: 10) { id } }", json!({})).unwrap(), 11);
+        assert_eq!(cost("{ projects { id } }", json!({})).unwrap(), 1 + DEFAULT_FIRST);
+        let source = "query($n: Int = 3) { projects(first: $n) { id name } }";
+        assert_eq!(cost(source, json!({})).unwrap(), 7);
+        assert_eq!(cost(source, json!({ "n": 4 })).unwrap(), 9);
+        // Reports cost more than other fields
+        let source = "{ project(owner: \"a\", repo: \"b\") { report { version } } }";
+        assert_eq!(cost(source, json!({})).unwrap(), 1 + REPORT_COST + 1);
+    }
+
+    #[test]
+    fn counts_aliased_fields_separately() {
+        let source = "{ a: projects(first: 1) { id } b: projects(first: 1) { id } }";
+        assert_eq!(cost(source, json!({})).unwrap(), 4);
+    }
+
+    #[test]
+    fn rejects_first_over_limit() {
+        let source = format!("{{ projects(first: {}) {{ id }} }}", MAX_FIRST + 1);
+        assert!(cost(&source, json!({})).is_err());
+        let source = "query($n: Int) { projects(first: $n) { id } }";
+        assert!(cost(source, json!({ "n": MAX_FIRST + 1 })).is_err());
+        assert!(cost(source, json!({ "n": -1 })).is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_fields_and_arguments() {
+        assert!(cost("{ projects { bogus } }", json!({})).is_err());
+        assert!(cost("{ bogus }", json!({})).is_err());
+        assert!(cost("{ projects(bogus: 1) { id } }", json!({})).is_err());
+        assert!(cost("{ projects { id(first: 1) } }", json!({})).is_err());
+        assert!(cost("{ projects { __typename } }", json!({})).is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_selections() {
+        // Objects need a selection and scalars can't have one
+        assert!(cost("{ projects }", json!({})).is_err());
+        assert!(cost("{ projects { id { a } } }", json!({})).is_err());
+    }
+
+    #[test]
+    fn rejects_undefined_variables() {
+        assert!(cost("{ projects(first: $n) { id } }", json!({ "n": 1 })).is_err());
+    }
+
+    #[test]
+    fn limits_value_depth() {
+        let mut value = parser::Value::Int(1);
+        for _ in 0..parser::MAX_DEPTH {
+            value = parser::Value::List(vec![value]);
+        }
+        assert!(to_json(&value, &Map::new()).is_err());
+    }
+}
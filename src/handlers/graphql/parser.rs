@@ -0,0 +1,407 @@
+//! Parser for the subset of GraphQL executable documents that the endpoint supports:
+//! query operations with variables, aliases, arguments and nested selections. Fragments,
+//! directives and mutations are rejected.
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, bail, Result};
+
+/// Maximum nesting of selection sets, values and types, checked while parsing so that deep
+/// documents can't overflow the stack.
+pub const MAX_DEPTH: usize = 16;
+
+#[derive(Debug, Clone)]
+pub struct Operation {
+    pub name: Option<String>,
+    pub variables: Vec<VariableDefinition>,
+    pub selection: Vec<Field>,
+}
+
+#[derive(Debug, Clone)]
+pub struct VariableDefinition {
+    pub name: String,
+    pub default: Option<Value>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub alias: Option<String>,
+    pub name: String,
+    pub arguments: Vec<(String, Value)>,
+    pub selection: Vec<Field>,
+}
+
+impl Field {
+    /// Key of the field in the response.
+    pub fn response_key(&self) -> &str { self.alias.as_deref().unwrap_or(&self.name) }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Variable(String),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Boolean(bool),
+    Null,
+    Enum(String),
+    List(Vec<Value>),
+    Object(BTreeMap<String, Value>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Punct(char),
+    Spread,
+    Name(String),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            // Commas are insignificant in GraphQL
+            ' ' | '\t' | '\n' | '\r' | ',' | '\u{feff}' => {
+                chars.next();
+            }
+            '#' => while chars.next_if(|&c| c != '\n' && c != '\r').is_some() {},
+            '{' | '}' | '(' | ')' | '[' | ']' | ':' | '!' | '$' | '=' | '@' | '|' | '&' => {
+                tokens.push(Token::Punct(c));
+                chars.next();
+            }
+            '.' => {
+                for _ in 0..3 {
+                    if chars.next() != Some('.') {
+                        bail!("Unexpected character '.'");
+                    }
+                }
+                tokens.push(Token::Spread);
+            }
+            '"' => {
+                chars.next();
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => value.push('\n'),
+                            Some('t') => value.push('\t'),
+                            Some('r') => value.push('\r'),
+                            Some('b') => value.push('\u{8}'),
+                            Some('f') => value.push('\u{c}'),
+                            Some('u') => {
+                                let hex = (0..4).filter_map(|_| chars.next()).collect::<String>();
+                                let code = u32::from_str_radix(&hex, 16)
+                                    .ok()
+                                    .and_then(char::from_u32)
+                                    .ok_or_else(|| anyhow!("Invalid unicode escape"))?;
+                                value.push(code);
+                            }
+                            Some(c @ ('"' | '\\' | '/')) => value.push(c),
+                            _ => bail!("Invalid escape sequence"),
+                        },
+                        Some('\n' | '\r') | None => bail!("Unterminated string"),
+                        Some(c) => value.push(c),
+                    }
+                }
+                tokens.push(Token::String(value));
+            }
+            '-' | '0'..='9' => {
+                let mut number = String::new();
+                while let Some(c) = chars
+                    .next_if(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+                {
+                    number.push(c);
+                }
+                if number.contains(['.', 'e', 'E']) {
+                    tokens.push(Token::Float(
+                        number.parse().map_err(|_| anyhow!("Invalid number {}", number))?,
+                    ));
+                } else {
+                    tokens.push(Token::Int(
+                        number.parse().map_err(|_| anyhow!("Invalid number {}", number))?,
+                    ));
+                }
+            }
+            c if c == '_' || c.is_ascii_alphabetic() => {
+                let mut name = String::new();
+                while let Some(c) = chars.next_if(|c| *c == '_' || c.is_ascii_alphanumeric()) {
+                    name.push(c);
+                }
+                tokens.push(Token::Name(name));
+            }
+            c => bail!("Unexpected character '{}'", c),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> { self.tokens.get(self.pos) }
+
+    fn next(&mut self) -> Result<Token> {
+        let token = self.tokens.get(self.pos).cloned().ok_or_else(|| anyhow!("Unexpected end"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(&Token::Punct(c)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            bail!("Expected '{}'", c)
+        }
+    }
+
+    fn name(&mut self) -> Result<String> {
+        match self.next()? {
+            Token::Name(name) => Ok(name),
+            token => bail!("Expected name, found {:?}", token),
+        }
+    }
+
+    fn operation(&mut self) -> Result<Operation> {
+        if self.peek() == Some(&Token::Punct('{')) {
+            let selection = self.selection_set(0)?;
+            return Ok(Operation { name: None, variables: vec![], selection });
+        }
+        match self.name()?.as_str() {
+            "query" => {}
+            "fragment" => bail!("Fragments are not supported"),
+            other => bail!("Unsupported operation type: {}", other),
+        }
+        let name = match self.peek() {
+            Some(Token::Name(_)) => Some(self.name()?),
+            _ => None,
+        };
+        let mut variables = Vec::new();
+        if self.eat('(') {
+            while !self.eat(')') {
+                self.expect('$')?;
+                let name = self.name()?;
+                self.expect(':')?;
+                self.skip_type(0)?;
+                let default = if self.eat('=') { Some(self.value(true, 0)?) } else { None };
+                variables.push(VariableDefinition { name, default });
+            }
+        }
+        if self.peek() == Some(&Token::Punct('@')) {
+            bail!("Directives are not supported");
+        }
+        let selection = self.selection_set(0)?;
+        Ok(Operation { name, variables, selection })
+    }
+
+    /// Variable types aren't checked, since arguments are validated when they're used.
+    fn skip_type(&mut self, depth: usize) -> Result<()> {
+        if depth >= MAX_DEPTH {
+            bail!("Type is nested too deeply");
+        }
+        if self.eat('[') {
+            self.skip_type(depth + 1)?;
+            self.expect(']')?;
+        } else {
+            self.name()?;
+        }
+        self.eat('!');
+        Ok(())
+    }
+
+    fn selection_set(&mut self, depth: usize) -> Result<Vec<Field>> {
+        if depth >= MAX_DEPTH {
+            bail!("Query is nested too deeply");
+        }
+        self.expect('{')?;
+        let mut fields = Vec::new();
+        while !self.eat('}') {
+            if self.peek() == Some(&Token::Spread) {
+                bail!("Fragments are not supported");
+            }
+            let mut name = self.name()?;
+            let mut alias = None;
+            if self.eat(':') {
+                alias = Some(name);
+                name = self.name()?;
+            }
+            let mut arguments = Vec::new();
+            if self.eat('(') {
+                while !self.eat(')') {
+                    let name = self.name()?;
+                    self.expect(':')?;
+                    arguments.push((name, self.value(false, 0)?));
+                }
+            }
+            if self.peek() == Some(&Token::Punct('@')) {
+                bail!("Directives are not supported");
+            }
+            let selection = if self.peek() == Some(&Token::Punct('{')) {
+                self.selection_set(depth + 1)?
+            } else {
+                vec![]
+            };
+            fields.push(Field { alias, name, arguments, selection });
+        }
+        if fields.is_empty() {
+            bail!("Selection set must not be empty");
+        }
+        Ok(fields)
+    }
+
+    fn value(&mut self, constant: bool, depth: usize) -> Result<Value> {
+        if depth >= MAX_DEPTH {
+            bail!("Value is nested too deeply");
+        }
+        Ok(match self.next()? {
+            Token::Punct('$') if !constant => Value::Variable(self.name()?),
+            Token::Int(i) => Value::Int(i),
+            Token::Float(f) => Value::Float(f),
+            Token::String(s) => Value::String(s),
+            Token::Name(name) => match name.as_str() {
+                "true" => Value::Boolean(true),
+                "false" => Value::Boolean(false),
+                "null" => Value::Null,
+                _ => Value::Enum(name),
+            },
+            Token::Punct('[') => {
+                let mut items = Vec::new();
+                while !self.eat(']') {
+                    items.push(self.value(constant, depth + 1)?);
+                }
+                Value::List(items)
+            }
+            Token::Punct('{') => {
+                let mut fields = BTreeMap::new();
+                while !self.eat('}') {
+                    let name = self.name()?;
+                    self.expect(':')?;
+                    fields.insert(name, self.value(constant, depth + 1)?);
+                }
+                Value::Object(fields)
+            }
+            token => bail!("Unexpected {:?}", token),
+        })
+    }
+}
+
+/// Parses a document and selects the operation to execute.
+pub fn parse(source: &str, operation_name: Option<&str>) -> Result<Operation> {
+    let mut parser = Parser { tokens: tokenize(source)?, pos: 0 };
+    let mut operations = Vec::new();
+    while parser.peek().is_some() {
+        operations.push(parser.operation()?);
+    }
+    match operation_name {
+        Some(name) => operations
+            .into_iter()
+            .find(|o| o.name.as_deref() == Some(name))
+            .ok_or_else(|| anyhow!("Unknown operation: {}", name)),
+        None if operations.len() == 1 => Ok(operations.remove(0)),
+        None if operations.is_empty() => bail!("Document contains no operations"),
+        None => bail!("Operation name is required when the document has several operations"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_nested_fields_with_aliases_and_arguments() {
+        let operation = parse(
+            r#"{ latest: project(owner: "zeldaret", repo: "tww") { name report { version } } }"#,
+            None,
+        )
+        .unwrap();
+        let [project] = operation.selection.as_slice() else { panic!("expected one field") };
+        assert_eq!(project.name, "project");
+        assert_eq!(project.response_key(), "latest");
+        assert_eq!(project.arguments, vec![
+            ("owner".to_string(), Value::String("zeldaret".to_string())),
+            ("repo".to_string(), Value::String("tww".to_string())),
+        ]);
+        let names = project.selection.iter().map(|f| f.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["name", "report"]);
+        assert_eq!(project.selection[1].selection[0].name, "version");
+    }
+
+    #[test]
+    fn parses_variables_with_defaults() {
+        let operation = parse(
+            "query Units($first: Int = 10, $name: String!) { projects(first: $first) { name } }",
+            None,
+        )
+        .unwrap();
+        assert_eq!(operation.name.as_deref(), Some("Units"));
+        assert_eq!(operation.variables.len(), 2);
+        assert_eq!(operation.variables[0].name, "first");
+        assert_eq!(operation.variables[0].default, Some(Value::Int(10)));
+        assert_eq!(operation.variables[1].default, None);
+        assert_eq!(operation.selection[0].arguments[0].1, Value::Variable("first".to_string()));
+    }
+
+    #[test]
+    fn rejects_variables_in_defaults() {
+        assert!(parse("query($a: Int = $b) { projects { name } }", None).is_err());
+    }
+
+    #[test]
+    fn selects_operation_by_name() {
+        let source = "query A { projects { name } } query B { projects { id } }";
+        assert_eq!(parse(source, Some("B")).unwrap().selection[0].selection[0].name, "id");
+        assert!(parse(source, None).is_err());
+        assert!(parse(source, Some("C")).is_err());
+    }
+
+    #[test]
+    fn rejects_deep_selections() {
+        let ok = format!("{}a{}", "{ a ".repeat(MAX_DEPTH), " }".repeat(MAX_DEPTH));
+        assert!(parse(&ok, None).is_ok());
+        let deep = format!("{}a{}", "{ a ".repeat(MAX_DEPTH + 1), " }".repeat(MAX_DEPTH + 1));
+        assert!(parse(&deep, None).is_err());
+    }
+
+    #[test]
+    fn rejects_deep_values() {
+        let value = format!("{}1{}", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH));
+        assert!(parse(&format!("{{ a(b: {}) }}", value), None).is_err());
+        let value = format!("{}1{}", "{a: ".repeat(MAX_DEPTH), "}".repeat(MAX_DEPTH));
+        assert!(parse(&format!("{{ a(b: {}) }}", value), None).is_err());
+        // Deep enough to overflow the stack without the limit
+        let value = format!("{}1{}", "[".repeat(100_000), "]".repeat(100_000));
+        assert!(parse(&format!("{{ a(b: {}) }}", value), None).is_err());
+    }
+
+    #[test]
+    fn rejects_deep_types() {
+        let ty = format!("{}Int{}", "[".repeat(100_000), "]".repeat(100_000));
+        assert!(parse(&format!("query($a: {}) {{ a }}", ty), None).is_err());
+    }
+
+    #[test]
+    fn rejects_unsupported_syntax() {
+        assert!(parse("{ ...Fields }", None).is_err());
+        assert!(parse("fragment F on Project { name }", None).is_err());
+        assert!(parse("mutation { a }", None).is_err());
+        assert!(parse("{ a @skip(if: true) }", None).is_err());
+        assert!(parse("{ }", None).is_err());
+        assert!(parse("{ a(b: \"unterminated) }", None).is_err());
+    }
+}
@@ -0,0 +1,184 @@
+use std::collections::{HashMap, HashSet};
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use mime::Mime;
+use objdiff_core::bindings::report::{ReportItem, ReportUnit};
+use serde::Serialize;
+
+use super::AppError;
+use crate::{models::ProjectInfo, AppState};
+
+/// Default number of unit revisions scanned per page of history.
+const DEFAULT_HISTORY_LIMIT: u32 = 100;
+/// Maximum number of unit revisions scanned per page of history.
+const MAX_HISTORY_LIMIT: u32 = 500;
+
+/// Which page of a unit's history to list, newest first.
+pub struct HistoryPage<'a> {
+    /// Only list changes before this commit
+    pub before: Option<&'a str>,
+    /// Number of unit revisions to scan
+    pub limit: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct UnitHistory<'a> {
+    unit: &'a str,
+    function: Option<&'a str>,
+    version: &'a str,
+    changes: Vec<UnitChange>,
+    /// Commit to pass as `before` for the previous page, if there are older changes
+    next_before: Option<String>,
+}
+
+#[derive(Serialize)]
+struct UnitChange {
+    commit: String,
+    timestamp: DateTime<Utc>,
+    /// Unit state after the commit, or None if the unit was removed
+    unit: Option<UnitState>,
+    /// Functions whose fuzzy match changed in the commit
+    functions: Vec<FunctionChange>,
+}
+
+#[derive(Serialize, Copy, Clone, PartialEq)]
+struct UnitState {
+    fuzzy_match_percent: f32,
+    complete: bool,
+    total_code: u64,
+    matched_code: u64,
+}
+
+#[derive(Serialize)]
+struct FunctionChange {
+    name: String,
+    demangled_name: Option<String>,
+    /// None if the function was added in the commit
+    previous_fuzzy_match_percent: Option<f32>,
+    /// None if the function was removed in the commit
+    fuzzy_match_percent: Option<f32>,
+}
+
+/// Lists the commits where a unit's (or one of its functions') match or completeness changed.
+/// Each page scans up to `limit` unit revisions, oldest first, and links to the page before it.
+pub async fn mode_history(
+    state: &AppState,
+    project_info: &ProjectInfo,
+    version: &str,
+    unit_name: &str,
+    function_name: Option<&str>,
+    page: HistoryPage<'_>,
+    acceptable: &[Mime],
+) -> Result<Response, AppError> {
+    if !acceptable.iter().any(|mime| {
+        (mime.type_() == mime::STAR && mime.subtype() == mime::STAR)
+            || (mime.type_() == mime::APPLICATION && mime.subtype() == mime::JSON)
+    }) {
+        return Err(AppError::Status(StatusCode::NOT_ACCEPTABLE));
+    }
+    let limit = page.limit.unwrap_or(DEFAULT_HISTORY_LIMIT).clamp(1, MAX_HISTORY_LIMIT) as usize;
+    // One extra revision is loaded as the baseline for the oldest change on the page
+    let entries = state
+        .db
+        .get_unit_history(project_info.project.id, version, unit_name, page.before, limit + 1)
+        .await?;
+    if entries.is_empty() {
+        return Err(AppError::Status(StatusCode::NOT_FOUND));
+    }
+    let skip = entries.len().saturating_sub(limit);
+    let next_before = (skip > 0).then(|| entries[skip].commit.sha.clone());
+    let mut changes = Vec::new();
+    let mut prev_unit: Option<&ReportUnit> = None;
+    let mut prev_state = None;
+    for (i, entry) in entries.iter().enumerate() {
+        let unit = entry.unit.as_deref();
+        let state = unit.map(unit_state);
+        let functions = function_changes(prev_unit, unit, function_name);
+        // When scoped to a function, only its changes are relevant
+        let unit_changed = function_name.is_none() && (i == 0 || state != prev_state);
+        if i >= skip && (unit_changed || !functions.is_empty()) {
+            changes.push(UnitChange {
+                commit: entry.commit.sha.clone(),
+                timestamp: entry.commit.timestamp,
+                unit: state,
+                functions,
+            });
+        }
+        prev_unit = unit;
+        prev_state = state;
+    }
+    if changes.is_empty() && next_before.is_none() && page.before.is_none() {
+        // The function was never present in the unit
+        return Err(AppError::Status(StatusCode::NOT_FOUND));
+    }
+    Ok(Json(UnitHistory {
+        unit: unit_name,
+        function: function_name,
+        version,
+        changes,
+        next_before,
+    })
+    .into_response())
+}
+
+fn unit_state(unit: &ReportUnit) -> UnitState {
+    let measures = unit.measures.unwrap_or_default();
+    UnitState {
+        fuzzy_match_percent: measures.fuzzy_match_percent,
+        complete: unit.metadata.as_ref().and_then(|m| m.complete).unwrap_or(false),
+        total_code: measures.total_code,
+        matched_code: measures.matched_code,
+    }
+}
+
+fn function_changes(
+    prev: Option<&ReportUnit>,
+    next: Option<&ReportUnit>,
+    function_name: Option<&str>,
+) -> Vec<FunctionChange> {
+    let prev = scoped_functions(prev, function_name);
+    let next = scoped_functions(next, function_name);
+    let prev_by_name = prev.iter().map(|f| (f.name.as_str(), *f)).collect::<HashMap<_, _>>();
+    let next_names = next.iter().map(|f| f.name.as_str()).collect::<HashSet<_>>();
+    let mut out = Vec::new();
+    for function in &next {
+        let previous = prev_by_name.get(function.name.as_str());
+        let previous_fuzzy_match_percent = previous.map(|f| f.fuzzy_match_percent);
+        if previous_fuzzy_match_percent == Some(function.fuzzy_match_percent) {
+            continue;
+        }
+        out.push(FunctionChange {
+            name: function.name.clone(),
+            demangled_name: function.metadata.as_ref().and_then(|m| m.demangled_name.clone()),
+            previous_fuzzy_match_percent,
+            fuzzy_match_percent: Some(function.fuzzy_match_percent),
+        });
+    }
+    for function in &prev {
+        if next_names.contains(function.name.as_str()) {
+            continue;
+        }
+        out.push(FunctionChange {
+            name: function.name.clone(),
+            demangled_name: function.metadata.as_ref().and_then(|m| m.demangled_name.clone()),
+            previous_fuzzy_match_percent: Some(function.fuzzy_match_percent),
+            fuzzy_match_percent: None,
+        });
+    }
+    out
+}
+
+fn scoped_functions<'a>(
+    unit: Option<&'a ReportUnit>,
+    function_name: Option<&str>,
+) -> Vec<&'a ReportItem> {
+    unit.into_iter()
+        .flat_map(|u| u.functions.iter())
+        .filter(|f| function_name.is_none_or(|name| f.name == name))
+        .collect()
+}
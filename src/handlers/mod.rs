@@ -1,9 +1,10 @@
-use std::{convert::Infallible, net::SocketAddr, str::FromStr, sync::Arc};
+use std::{convert::Infallible, net::SocketAddr, str::FromStr, sync::Arc, time::Duration};
 
 use axum::{
     async_trait,
     extract::{ConnectInfo, FromRequestParts, OriginalUri},
     http::{header, request::Parts, HeaderMap, StatusCode, Uri},
+    middleware,
     response::{IntoResponse, Response},
     routing::get,
     Extension, Router,
@@ -11,28 +12,55 @@ use axum::{
 use bytes::BytesMut;
 use mime::Mime;
 use prost::Message;
+use tower_http::timeout::TimeoutLayer;
 
-use crate::AppState;
+use crate::{blocking::Busy, config::LimitsConfig, AppState};
 
+mod api;
 mod assets;
 mod badge;
+mod bar;
+mod cache;
+mod card;
+mod compare;
 mod css;
+mod dashboard;
+mod graphql;
+mod history;
 mod js;
 mod project;
+mod recommend;
 mod report;
+mod search;
+mod stats;
 mod treemap;
 
-pub fn build_router() -> Router<AppState> {
+pub fn build_router(limits: &LimitsConfig) -> Router<AppState> {
+    // Report URLs may render images and animations, so they get a longer timeout
+    let reports = Router::new()
+        .route("/:owner/:repo", get(report::get_report))
+        .route("/:owner/:repo/:version", get(report::get_report))
+        .route("/:owner/:repo/:version/:commit", get(report::get_report))
+        .layer(TimeoutLayer::new(Duration::from_secs(limits.render_timeout)));
     Router::new()
         .route("/css/*filename", get(css::get_css))
         .route("/js/*filename", get(js::get_js))
         .route("/assets/*filename", get(assets::get_asset))
         .route("/", get(project::get_projects))
-        .route("/:owner/:repo", get(report::get_report))
-        .route("/:owner/:repo/:version", get(report::get_report))
-        .route("/:owner/:repo/:version/:commit", get(report::get_report))
+        .route("/platform/:platform", get(project::get_platform))
+        .route("/dashboard", get(dashboard::get_dashboard))
+        .route("/search", get(search::get_search))
+        .nest("/api/v1", api::build_router())
+        .route("/api/projects", get(project::get_projects_api))
+        .route("/api/stats", get(stats::get_stats))
+        .layer(TimeoutLayer::new(Duration::from_secs(limits.request_timeout)))
+        .merge(reports)
+        .layer(middleware::from_fn(cache::conditional))
 }
 
+/// Seconds clients are asked to wait when the blocking pool is saturated.
+const RETRY_AFTER_SECS: u64 = 5;
+
 pub enum AppError {
     Status(StatusCode),
     Internal(anyhow::Error),
@@ -45,6 +73,15 @@ impl IntoResponse for AppError {
                 (status, "Not found").into_response()
             }
             Self::Status(status) => status.into_response(),
+            Self::Internal(err) if err.is::<Busy>() => {
+                tracing::warn!("{}", err);
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    [(header::RETRY_AFTER, RETRY_AFTER_SECS.to_string())],
+                    "Server is busy, try again later",
+                )
+                    .into_response()
+            }
             Self::Internal(err) => {
                 tracing::error!("{:?}", err);
                 (StatusCode::INTERNAL_SERVER_ERROR, format!("Something went wrong: {}", err))
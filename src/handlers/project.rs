@@ -1,24 +1,45 @@
-use std::{sync::Arc, time::Instant};
+use std::{collections::HashMap, str::FromStr, time::Instant};
 
-use anyhow::{anyhow, Context};
+use anyhow::Context;
 use axum::{
-    extract::{Query, State},
-    http::StatusCode,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode, Uri},
     response::{Html, IntoResponse, Response},
+    Json,
 };
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeDelta, Utc};
+use mime::Mime;
 use objdiff_core::bindings::report::Measures;
 use serde::{Deserialize, Serialize};
-use tokio::{sync::Semaphore, task::JoinSet};
+use url::Url;
 
-use super::AppError;
-use crate::{templates::render, AppState};
+use super::{parse_accept, AppError, FullUri};
+use crate::{models::ProjectSummary, templates::render, util::UrlExt, AppState};
+
+/// Display names for the platforms in `assets/platforms`.
+const PLATFORM_NAMES: &[(&str, &str)] = &[
+    ("gc", "GameCube"),
+    ("wii", "Wii"),
+    ("n64", "Nintendo 64"),
+    ("psx", "PlayStation"),
+    ("switch", "Switch"),
+];
+
+fn platform_name(id: &str) -> &str {
+    PLATFORM_NAMES.iter().find(|(k, _)| *k == id).map_or(id, |(_, name)| name)
+}
 
 #[derive(Serialize)]
-struct ProjectsTemplateContext {
+struct ProjectsTemplateContext<'a> {
     projects: Vec<ProjectInfoContext>,
-    sort_options: &'static [SortOption],
+    sort_options: Vec<SortLink>,
     current_sort: SortOption,
+    platforms: Vec<PlatformItem<'a>>,
+    current_platform: Option<PlatformItem<'a>>,
+    platform_locked: bool,
+    query: &'a str,
+    min: Option<f32>,
+    max: Option<f32>,
 }
 
 #[derive(Serialize)]
@@ -33,11 +54,24 @@ struct ProjectInfoContext {
     timestamp: DateTime<Utc>,
     measures: Measures,
     platform: Option<String>,
+    /// Version that `measures` belongs to
+    default_version: Option<String>,
+    /// Versions with a report at the latest commit
+    versions: Vec<String>,
+    /// Matched code percentage points gained in the last month, if sorting by it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recent_gain: Option<f32>,
 }
 
 #[derive(Deserialize)]
 pub struct ProjectsQuery {
     sort: Option<String>,
+    platform: Option<String>,
+    q: Option<String>,
+    /// Minimum matched code percentage
+    min: Option<String>,
+    /// Maximum matched code percentage
+    max: Option<String>,
 }
 
 #[derive(Serialize, Copy, Clone)]
@@ -46,21 +80,137 @@ struct SortOption {
     name: &'static str,
 }
 
+#[derive(Serialize)]
+struct SortLink {
+    key: &'static str,
+    name: &'static str,
+    path: String,
+}
+
+#[derive(Serialize)]
+struct PlatformItem<'a> {
+    id: &'a str,
+    name: &'a str,
+    path: String,
+}
+
 const SORT_OPTIONS: &[SortOption] = &[
     SortOption { key: "updated", name: "Last updated" },
     SortOption { key: "matched_code", name: "Matched Code" },
+    SortOption { key: "matched_data", name: "Matched Data" },
+    SortOption { key: "complete_units", name: "Completed Units" },
+    SortOption { key: "recent_gain", name: "Recent Gain" },
     SortOption { key: "name", name: "Name" },
 ];
 
 pub async fn get_projects(
     State(state): State<AppState>,
+    headers: HeaderMap,
+    FullUri(uri): FullUri,
     Query(query): Query<ProjectsQuery>,
+) -> Result<Response, AppError> {
+    let acceptable = parse_accept(&headers, None);
+    let platform = query.platform.clone().filter(|p| !p.is_empty());
+    project_list(state, uri, query, platform, false, &acceptable).await
+}
+
+pub async fn get_projects_api(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    FullUri(uri): FullUri,
+    Query(query): Query<ProjectsQuery>,
+) -> Result<Response, AppError> {
+    let acceptable = parse_accept(&headers, Some("json"));
+    let platform = query.platform.clone().filter(|p| !p.is_empty());
+    project_list(state, uri, query, platform, false, &acceptable).await
+}
+
+pub async fn get_platform(
+    State(state): State<AppState>,
+    Path(platform): Path<String>,
+    headers: HeaderMap,
+    FullUri(uri): FullUri,
+    Query(query): Query<ProjectsQuery>,
+) -> Result<Response, AppError> {
+    let acceptable = parse_accept(&headers, None);
+    project_list(state, uri, query, Some(platform), true, &acceptable).await
+}
+
+/// Parses an optional form value, treating an empty value as absent.
+fn parse_param<T: FromStr>(value: Option<&str>) -> Result<Option<T>, AppError> {
+    match value.map(str::trim).filter(|v| !v.is_empty()) {
+        Some(value) => {
+            value.parse().map(Some).map_err(|_| AppError::Status(StatusCode::BAD_REQUEST))
+        }
+        None => Ok(None),
+    }
+}
+
+async fn project_list(
+    state: AppState,
+    uri: Uri,
+    query: ProjectsQuery,
+    platform: Option<String>,
+    platform_locked: bool,
+    acceptable: &[Mime],
 ) -> Result<Response, AppError> {
     let start = Instant::now();
+    if acceptable.is_empty() {
+        return Err(AppError::Status(StatusCode::NOT_ACCEPTABLE));
+    }
+    let current_sort_key = query.sort.as_deref().unwrap_or("updated");
+    let current_sort = SORT_OPTIONS
+        .iter()
+        .find(|s| s.key.eq_ignore_ascii_case(current_sort_key))
+        .copied()
+        .ok_or(AppError::Status(StatusCode::BAD_REQUEST))?;
+    let min = parse_param::<f32>(query.min.as_deref())?;
+    let max = parse_param::<f32>(query.max.as_deref())?;
+    let needle = query.q.as_deref().map(str::trim).unwrap_or_default();
+    let needle_lower = needle.to_lowercase();
+
     let projects = state.db.get_projects().await?;
-    let mut out = projects
+    let mut platform_ids =
+        projects.iter().filter_map(|p| p.info.project.platform.as_deref()).collect::<Vec<_>>();
+    platform_ids.sort_unstable();
+    platform_ids.dedup();
+    if platform_locked && !platform_ids.iter().any(|id| Some(*id) == platform.as_deref()) {
+        return Err(AppError::Status(StatusCode::NOT_FOUND));
+    }
+    let filtered = projects
         .iter()
-        .filter_map(|p| {
+        .filter(|p| platform.is_none() || p.info.project.platform.as_deref() == platform.as_deref())
+        .filter(|p| {
+            let project = &p.info.project;
+            needle_lower.is_empty()
+                || project.name().to_lowercase().contains(&needle_lower)
+                || project.short_name().to_lowercase().contains(&needle_lower)
+                || format!("{}/{}", project.owner, project.repo)
+                    .to_lowercase()
+                    .contains(&needle_lower)
+        })
+        .filter(|p| min.is_none_or(|min| p.measures.matched_code_percent >= min))
+        .filter(|p| max.is_none_or(|max| p.measures.matched_code_percent <= max))
+        .collect::<Vec<_>>();
+    // Only load the history when it's needed
+    let mut recent_gains = HashMap::new();
+    if current_sort.key == "recent_gain" {
+        let baselines = state.db.get_measures_at(Utc::now() - TimeDelta::days(30)).await?;
+        for ProjectSummary { info, measures } in &filtered {
+            let Some(version) = info.default_version() else {
+                continue;
+            };
+            let baseline =
+                baselines.get(&(info.project.id, version.to_string())).copied().unwrap_or_default();
+            recent_gains.insert(
+                info.project.id,
+                measures.matched_code_percent - baseline.matched_code_percent,
+            );
+        }
+    }
+    let mut out = filtered
+        .into_iter()
+        .filter_map(|ProjectSummary { info: p, measures }| {
             let commit = p.commit.as_ref()?;
             Some(ProjectInfoContext {
                 id: p.project.id,
@@ -71,80 +221,86 @@ pub async fn get_projects(
                 short_name: p.project.short_name().to_owned(),
                 commit: commit.sha.clone(),
                 timestamp: commit.timestamp,
-                measures: Default::default(),
+                measures: *measures,
                 platform: p.project.platform.clone(),
+                default_version: p.default_version().map(str::to_string),
+                versions: p.report_versions.clone(),
+                recent_gain: recent_gains.get(&p.project.id).copied(),
             })
         })
         .collect::<Vec<_>>();
 
-    // Fetch latest report for each
-    let sem = Arc::new(Semaphore::new(10));
-    let mut join_set = JoinSet::new();
-    for info in projects {
-        let sem = sem.clone();
-        let state = state.clone();
-        join_set.spawn(async move {
-            let _permit = sem.acquire().await;
-            let Some(version) = info.default_version() else {
-                return (info, Err(anyhow!("No report version found")));
-            };
-            let commit = info.commit.as_ref().unwrap();
-            let report = state
-                .db
-                .get_report(&info.project.owner, &info.project.repo, &commit.sha, version)
-                .await
-                .with_context(|| {
-                    format!(
-                        "Failed to fetch report for {}/{} sha {} version {}",
-                        info.project.owner, info.project.repo, commit.sha, version
-                    )
-                });
-            (info, report)
-        });
-    }
-    while let Some(result) = join_set.join_next().await {
-        match result {
-            Ok((info, Ok(Some(file)))) => {
-                if let Some(c) = out.iter_mut().find(|i| i.id == info.project.id) {
-                    c.measures = file.report.measures.unwrap_or_default();
-                }
-            }
-            Ok((info, Ok(None))) => {
-                tracing::warn!("No report found for {}", info.project.id);
-            }
-            Ok((info, Err(e))) => {
-                tracing::error!("Failed to fetch report for {}: {:?}", info.project.id, e);
-            }
-            Err(e) => {
-                tracing::error!("Failed to fetch report: {:?}", e);
-            }
+    let by_percent = |f: fn(&Measures) -> f32| {
+        move |a: &ProjectInfoContext, b: &ProjectInfoContext| {
+            f(&b.measures).partial_cmp(&f(&a.measures)).unwrap_or(std::cmp::Ordering::Equal)
         }
-    }
-
-    let current_sort_key = query.sort.as_deref().unwrap_or("updated");
-    let current_sort = SORT_OPTIONS
-        .iter()
-        .find(|s| s.key.eq_ignore_ascii_case(current_sort_key))
-        .copied()
-        .ok_or(AppError::Status(StatusCode::BAD_REQUEST))?;
+    };
     match current_sort.key {
         "name" => out.sort_by(|a, b| a.name.cmp(&b.name)),
-        "updated" => out.sort_by(|a, b| b.timestamp.cmp(&a.timestamp)),
-        "matched_code" => out.sort_by(|a, b| {
-            b.measures
-                .matched_code_percent
-                .partial_cmp(&a.measures.matched_code_percent)
-                .unwrap_or(std::cmp::Ordering::Equal)
+        "updated" => out.sort_by_key(|p| std::cmp::Reverse(p.timestamp)),
+        "matched_code" => out.sort_by(by_percent(|m| m.matched_code_percent)),
+        "matched_data" => out.sort_by(by_percent(|m| m.matched_data_percent)),
+        "complete_units" => out.sort_by(by_percent(|m| {
+            if m.total_units == 0 {
+                0.0
+            } else {
+                m.complete_units as f32 / m.total_units as f32
+            }
+        })),
+        "recent_gain" => out.sort_by(|a, b| {
+            b.recent_gain.partial_cmp(&a.recent_gain).unwrap_or(std::cmp::Ordering::Equal)
         }),
         _ => return Err(AppError::Status(StatusCode::BAD_REQUEST)),
     }
 
-    let mut rendered = render(&state.templates, "projects.html", ProjectsTemplateContext {
-        projects: out,
-        sort_options: SORT_OPTIONS,
-        current_sort,
-    })?;
-    let elapsed = start.elapsed();
-    rendered = rendered.replace("[[time]]", &format!("{}ms", elapsed.as_millis()));
-    Ok(Html(rendered).into_response())
+    for mime in acceptable {
+        if (mime.type_() == mime::STAR && mime.subtype() == mime::STAR)
+            || (mime.type_() == mime::TEXT && mime.subtype() == mime::HTML)
+        {
+            let request_url = Url::parse(&uri.to_string()).context("Failed to parse URI")?;
+            let sort_options = SORT_OPTIONS
+                .iter()
+                .map(|o| SortLink {
+                    key: o.key,
+                    name: o.name,
+                    path: request_url.query_param("sort", Some(o.key)).path_and_query().to_string(),
+                })
+                .collect();
+            let platforms = platform_ids
+                .iter()
+                .map(|&id| PlatformItem {
+                    id,
+                    name: platform_name(id),
+                    path: request_url
+                        .with_path(&format!("/platform/{}", id))
+                        .query_param("platform", None)
+                        .path_and_query()
+                        .to_string(),
+                })
+                .collect::<Vec<_>>();
+            let current_platform = platform.as_deref().map(|id| PlatformItem {
+                id,
+                name: platform_name(id),
+                path: format!("/platform/{}", id),
+            });
+            let mut rendered =
+                render(&state.templates, "projects.html", ProjectsTemplateContext {
+                    projects: out,
+                    sort_options,
+                    current_sort,
+                    platforms,
+                    current_platform,
+                    platform_locked,
+                    query: needle,
+                    min,
+                    max,
+                })?;
+            let elapsed = start.elapsed();
+            rendered = rendered.replace("[[time]]", &format!("{}ms", elapsed.as_millis()));
+            return Ok(Html(rendered).into_response());
+        } else if mime.type_() == mime::APPLICATION && mime.subtype() == mime::JSON {
+            return Ok(Json(out).into_response());
+        }
+    }
+    Err(AppError::Status(StatusCode::NOT_ACCEPTABLE))
 }
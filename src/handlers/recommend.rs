@@ -0,0 +1,171 @@
+use std::time::Instant;
+
+use anyhow::Context;
+use axum::{
+    http::{StatusCode, Uri},
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use mime::Mime;
+use objdiff_core::bindings::report::{ReportCategory, ReportUnit};
+use serde::Serialize;
+use url::Url;
+
+use super::AppError;
+use crate::{
+    models::{ProjectInfo, ReportFile},
+    templates::render,
+    util::UrlExt,
+    AppState,
+};
+
+const DEFAULT_LIMIT: u32 = 50;
+const MAX_LIMIT: u32 = 500;
+/// Number of recent reports to consider when looking for recently changed units.
+pub(super) const RECENT_REPORTS: u32 = 10;
+/// Function size in bytes at which the size score halves.
+const SIZE_SCALE: f32 = 256.0;
+
+#[derive(Serialize)]
+struct Recommendation<'a> {
+    unit: &'a str,
+    name: &'a str,
+    demangled_name: Option<&'a str>,
+    size: u64,
+    fuzzy_match_percent: f32,
+    unit_matched_code_percent: f32,
+    unit_recently_changed: bool,
+    score: f32,
+    unit_path: String,
+    function_path: String,
+    source_file_url: Option<String>,
+}
+
+#[derive(Serialize)]
+struct RecommendationsTemplateContext<'a> {
+    project_short_name: &'a str,
+    project_path: &'a str,
+    version: &'a str,
+    report_path: &'a str,
+    current_unit: Option<&'a str>,
+    recommendations: &'a [Recommendation<'a>],
+}
+
+/// Ranks the unmatched functions in scope by how promising they are to work on next.
+#[allow(clippy::too_many_arguments)]
+pub async fn mode_recommendations(
+    state: &AppState,
+    report: &ReportFile,
+    project_info: &ProjectInfo,
+    current_category: Option<&ReportCategory>,
+    current_unit: Option<&ReportUnit>,
+    limit: Option<u32>,
+    uri: Uri,
+    start: Instant,
+    acceptable: &[Mime],
+) -> Result<Response, AppError> {
+    let recently_changed = state
+        .db
+        .get_recently_changed_units(
+            project_info.project.id,
+            &report.version,
+            report.commit.timestamp,
+            RECENT_REPORTS,
+        )
+        .await?;
+    let request_url = Url::parse(&uri.to_string()).context("Failed to parse URI")?;
+    let report_url = request_url
+        .with_path(&format!(
+            "/{}/{}/{}/{}",
+            project_info.project.owner,
+            project_info.project.repo,
+            report.version,
+            report.commit.sha
+        ))
+        .query_param("mode", None)
+        .query_param("limit", None);
+
+    let units = match current_unit {
+        Some(unit) => vec![unit],
+        None => report
+            .report
+            .units
+            .iter()
+            .filter(|unit| {
+                current_category.is_none_or(|category| {
+                    unit.metadata
+                        .as_ref()
+                        .is_some_and(|m| m.progress_categories.contains(&category.id))
+                })
+            })
+            .collect(),
+    };
+    let mut recommendations = Vec::new();
+    for unit in units {
+        let unit_matched_code_percent =
+            unit.measures.as_ref().map_or(0.0, |m| m.matched_code_percent);
+        let unit_recently_changed = recently_changed.contains(&unit.name);
+        let unit_url = report_url.query_param("unit", Some(&unit.name));
+        let source_file_url = unit
+            .metadata
+            .as_ref()
+            .and_then(|m| m.source_path.as_deref())
+            .map(|path| project_info.project.source_url(&report.commit.sha, path));
+        for function in &unit.functions {
+            if function.size == 0 || function.fuzzy_match_percent >= 100.0 {
+                continue;
+            }
+            let size_score = SIZE_SCALE / (SIZE_SCALE + function.size as f32);
+            let score = 0.4 * function.fuzzy_match_percent / 100.0
+                + 0.25 * size_score
+                + 0.2 * unit_matched_code_percent / 100.0
+                + if unit_recently_changed { 0.15 } else { 0.0 };
+            recommendations.push(Recommendation {
+                unit: &unit.name,
+                name: &function.name,
+                demangled_name: function
+                    .metadata
+                    .as_ref()
+                    .and_then(|m| m.demangled_name.as_deref()),
+                size: function.size,
+                fuzzy_match_percent: function.fuzzy_match_percent,
+                unit_matched_code_percent,
+                unit_recently_changed,
+                score,
+                unit_path: unit_url.path_and_query().to_string(),
+                function_path: unit_url
+                    .query_param("function", Some(&function.name))
+                    .path_and_query()
+                    .to_string(),
+                source_file_url: source_file_url.clone(),
+            });
+        }
+    }
+    recommendations
+        .sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+    recommendations.truncate(limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT) as usize);
+
+    for mime in acceptable {
+        if (mime.type_() == mime::STAR && mime.subtype() == mime::STAR)
+            || (mime.type_() == mime::TEXT && mime.subtype() == mime::HTML)
+        {
+            let project_path =
+                format!("/{}/{}", project_info.project.owner, project_info.project.repo);
+            let mut rendered =
+                render(&state.templates, "recommendations.html", RecommendationsTemplateContext {
+                    project_short_name: project_info.project.short_name(),
+                    project_path: &project_path,
+                    version: &report.version,
+                    report_path: report_url.path_and_query(),
+                    current_unit: current_unit.map(|u| u.name.as_str()),
+                    recommendations: &recommendations,
+                })?;
+            let elapsed = start.elapsed();
+            rendered = rendered.replace("[[time]]", &format!("{}ms", elapsed.as_millis()));
+            return Ok(Html(rendered).into_response());
+        } else if mime.type_() == mime::APPLICATION && mime.subtype() == mime::JSON {
+            return Ok(Json(recommendations).into_response());
+        }
+    }
+    Err(AppError::Status(StatusCode::NOT_ACCEPTABLE))
+}
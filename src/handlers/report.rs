@@ -1,4 +1,4 @@
-use std::{borrow::Cow, iter, time::Instant};
+use std::{borrow::Cow, collections::BTreeMap, iter, time::Instant};
 
 use anyhow::{Context, Result};
 use axum::{
@@ -7,15 +7,22 @@ use axum::{
     response::{Html, IntoResponse, Response},
     Json,
 };
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
 use image::ImageFormat;
 use mime::Mime;
-use objdiff_core::bindings::report::{Measures, ReportCategory, ReportUnit};
+use objdiff_core::bindings::report::{Measures, ReportCategory, ReportItem, ReportUnit};
 use serde::{Deserialize, Serialize};
 use url::Url;
 
-use super::{badge, parse_accept, treemap, AppError, FullUri, Protobuf, PROTOBUF};
+use super::{
+    badge, bar, cache, card, compare, history, parse_accept, recommend,
+    treemap::{self, TreemapColors},
+    AppError, FullUri, Protobuf, PROTOBUF,
+};
 use crate::{
-    models::{Project, ProjectInfo, ReportFile},
+    blocking::Task,
+    models::{Commit, Project, ProjectInfo, ReportFile},
+    svg,
     templates::render,
     util::UrlExt,
     AppState,
@@ -31,6 +38,12 @@ pub struct ReportParams {
 
 const DEFAULT_IMAGE_WIDTH: u32 = 950;
 const DEFAULT_IMAGE_HEIGHT: u32 = 475;
+const DEFAULT_TIMELAPSE_FRAMES: u32 = 30;
+const MAX_TIMELAPSE_FRAMES: u32 = 60;
+/// Default delay between timelapse frames, in milliseconds.
+const DEFAULT_TIMELAPSE_DELAY: u32 = 250;
+/// Number of unit revisions scanned for a function's match history.
+const MAX_FUNCTION_HISTORY: usize = 100;
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -42,12 +55,52 @@ pub struct ReportQuery {
     #[serde(flatten)]
     shield: badge::ShieldParams,
     unit: Option<String>,
+    function: Option<String>,
+    path: Option<String>,
+    view: Option<String>,
+    sort: Option<String>,
+    order: Option<String>,
+    complete: Option<bool>,
+    q: Option<String>,
+    page: Option<u32>,
+    limit: Option<u32>,
+    /// History is paginated by commit, listing changes before this one
+    before: Option<String>,
+    scheme: Option<String>,
+    theme: Option<String>,
+    binary: Option<bool>,
+    labels: Option<bool>,
+    from: Option<String>,
+    to: Option<String>,
+    frames: Option<u32>,
+    interval: Option<String>,
+    delay: Option<u32>,
+    categories: Option<bool>,
 }
 
 impl ReportQuery {
+    pub fn unit(&self) -> Option<&str> { self.unit.as_deref() }
+
+    /// The requested image size, which `report_response` has checked is in range.
     pub fn size(&self) -> (u32, u32) {
         (self.w.unwrap_or(DEFAULT_IMAGE_WIDTH), self.h.unwrap_or(DEFAULT_IMAGE_HEIGHT))
     }
+
+    /// Returns None if the colour scheme or theme is invalid.
+    fn treemap_options(&self) -> Option<treemap::TreemapOptions> {
+        let theme = match self.theme.as_deref() {
+            Some(theme) => Some(treemap::Theme::from_query(theme)?),
+            None => None,
+        };
+        Some(treemap::TreemapOptions {
+            colors: treemap::TreemapColors {
+                scheme: treemap::ColorScheme::from_query(self.scheme.as_deref())?,
+                binary: self.binary.unwrap_or(false),
+            },
+            theme,
+            labels: self.labels.unwrap_or(false),
+        })
+    }
 }
 
 #[derive(Serialize)]
@@ -70,8 +123,22 @@ struct ReportTemplateContext<'a> {
     canonical_path: &'a str,
     canonical_url: &'a str,
     image_url: &'a str,
+    card_url: &'a str,
+    card_width: u32,
+    card_height: u32,
     current_unit: Option<&'a str>,
+    current_function: Option<&'a ReportTemplateFunction<'a>>,
     units_path: &'a str,
+    functions_path: &'a str,
+    current_path: Option<String>,
+    path_items: &'a [ReportPathItem<'a>],
+    view_toggle_path: &'a str,
+    view: TreemapView,
+    view_paths: &'a [ReportTemplateVersion<'a>],
+    list_path: &'a str,
+    recommendations_path: &'a str,
+    compare_path: &'a str,
+    treemap_data_path: &'a str,
     commit_message: Option<&'a str>,
     commit_url: &'a str,
     source_file_url: Option<&'a str>,
@@ -79,14 +146,44 @@ struct ReportTemplateContext<'a> {
 
 #[derive(Serialize)]
 pub struct ReportTemplateUnit<'a> {
+    /// Unit name, mangled function name or directory path
+    id: Cow<'a, str>,
+    pub(super) name: Cow<'a, str>,
+    /// Code or data size, depending on the view
+    size: u64,
+    #[serde(skip)]
+    matched_size: u64,
+    #[serde(skip)]
+    complete: bool,
+    fuzzy_match_percent: f32,
+    pub(super) color: String,
+    pub(super) directory: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    children: Vec<ReportTemplateUnit<'a>>,
+    pub(super) x: f32,
+    pub(super) y: f32,
+    pub(super) w: f32,
+    pub(super) h: f32,
+}
+
+#[derive(Serialize)]
+struct ReportTemplateFunction<'a> {
     name: &'a str,
-    total_code: u64,
+    demangled_name: Option<&'a str>,
+    size: u64,
     fuzzy_match_percent: f32,
-    color: String,
-    x: f32,
-    y: f32,
-    w: f32,
-    h: f32,
+    address: Option<String>,
+    history: Vec<FunctionHistoryItem>,
+}
+
+#[derive(Serialize)]
+struct FunctionHistoryItem {
+    commit: String,
+    timestamp: DateTime<Utc>,
+    path: String,
+    /// None if the function didn't exist in this commit
+    size: Option<u64>,
+    fuzzy_match_percent: Option<f32>,
 }
 
 #[derive(Serialize, Clone)]
@@ -96,6 +193,12 @@ struct ReportCategoryItem<'a> {
     path: String,
 }
 
+#[derive(Serialize)]
+struct ReportPathItem<'a> {
+    name: &'a str,
+    path: String,
+}
+
 #[derive(Serialize, Clone)]
 struct ReportTemplateVersion<'a> {
     id: &'a str,
@@ -193,9 +296,33 @@ pub async fn get_report(
     FullUri(uri): FullUri,
     State(state): State<AppState>,
 ) -> Result<Response, AppError> {
-    let start = Instant::now();
     let (params, ext) = extract_extension(params);
     let acceptable = parse_accept(&headers, ext.as_deref());
+    report_response(state, params, query, acceptable, uri, headers).await
+}
+
+/// Responds to an API request for a report in the given mode, always as JSON.
+pub async fn get_report_json(
+    state: AppState,
+    params: ReportParams,
+    query: ReportQuery,
+    mode: &str,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let query = ReportQuery { mode: Some(mode.to_string()), ..query };
+    report_response(state, params, query, vec![mime::APPLICATION_JSON], uri, headers).await
+}
+
+async fn report_response(
+    state: AppState,
+    params: ReportParams,
+    query: ReportQuery,
+    acceptable: Vec<Mime>,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let start = Instant::now();
     if acceptable.is_empty() {
         return Err(AppError::Status(StatusCode::NOT_ACCEPTABLE));
     }
@@ -222,18 +349,150 @@ pub async fn get_report(
     } else {
         return Err(AppError::Status(StatusCode::NOT_FOUND));
     };
-    let Some(report) =
-        state.db.get_report(&params.owner, &params.repo, &commit.sha, version).await?
-    else {
+    if query.function.is_some() && query.unit.is_none() {
+        return Err(AppError::Status(StatusCode::BAD_REQUEST));
+    }
+    if [query.w, query.h].into_iter().flatten().any(|size| !svg::is_valid_size(size)) {
+        return Err(AppError::Status(StatusCode::BAD_REQUEST));
+    }
+    let mode = query.mode.as_deref().unwrap_or("report").to_ascii_lowercase();
+    let view = TreemapView::from_query(query.view.as_deref())
+        .ok_or(AppError::Status(StatusCode::BAD_REQUEST))?;
+    let treemap_options =
+        query.treemap_options().ok_or(AppError::Status(StatusCode::BAD_REQUEST))?;
+    if mode == "history" {
+        let unit = query.unit.as_deref().ok_or(AppError::Status(StatusCode::BAD_REQUEST))?;
+        let function = query.function.as_deref();
+        let page = history::HistoryPage { before: query.before.as_deref(), limit: query.limit };
+        return history::mode_history(
+            &state,
+            &project_info,
+            version,
+            unit,
+            function,
+            page,
+            &acceptable,
+        )
+        .await;
+    }
+    if mode == "compare" {
+        return compare::mode_compare(
+            &state,
+            &project_info,
+            version,
+            query.category.as_deref(),
+            query.limit,
+            uri,
+            start,
+            &acceptable,
+        )
+        .await;
+    }
+    if mode == "timelapse" {
+        return mode_timelapse(
+            &state,
+            &project_info,
+            version,
+            &query,
+            view,
+            treemap_options,
+            &acceptable,
+        )
+        .await;
+    }
+    // Responses for an explicit commit and version never change
+    let immutable = params.commit.as_deref().is_some_and(|c| c.eq_ignore_ascii_case(&commit.sha))
+        && params.version.as_deref().is_some_and(|v| !v.eq_ignore_ascii_case("default"));
+    let policy = if immutable {
+        cache::CachePolicy::Immutable(cache::immutable_etag(&uri, &acceptable))
+    } else {
+        cache::CachePolicy::Latest(commit.timestamp)
+    };
+    if let Some(response) = cache::check_immutable(&headers, policy)? {
+        return Ok(response);
+    }
+    // Only load the units that the response needs
+    let (owner, repo, sha) = (&params.owner, &params.repo, &commit.sha);
+    let report = match (mode.as_str(), query.unit.as_deref()) {
+        ("shield", None) if query.path.is_none() => {
+            state.db.get_report_header(owner, repo, sha, version).await?
+        }
+        ("shield", None) => state.db.get_report(owner, repo, sha, version).await?,
+        ("shield", Some(unit)) => state.db.get_report_unit(owner, repo, sha, version, unit).await?,
+        ("report", Some(unit)) if !wants_report_data(&acceptable) => {
+            state.db.get_report_unit(owner, repo, sha, version, unit).await?
+        }
+        ("report", _) => state.db.get_report(owner, repo, sha, version).await?,
+        ("units", Some(unit)) => state.db.get_report_unit(owner, repo, sha, version, unit).await?,
+        ("units", None) => state.db.get_report(owner, repo, sha, version).await?,
+        ("recommendations", Some(unit)) => {
+            state.db.get_report_unit(owner, repo, sha, version, unit).await?
+        }
+        ("recommendations", None) => state.db.get_report(owner, repo, sha, version).await?,
+        ("treemap", Some(unit)) => {
+            state.db.get_report_unit(owner, repo, sha, version, unit).await?
+        }
+        ("treemap", None) => state.db.get_report(owner, repo, sha, version).await?,
+        ("bar", None) if query.path.is_none() => {
+            state.db.get_report_header(owner, repo, sha, version).await?
+        }
+        ("bar", None) => state.db.get_report(owner, repo, sha, version).await?,
+        ("bar", Some(unit)) => state.db.get_report_unit(owner, repo, sha, version, unit).await?,
+        ("card", Some(unit)) => state.db.get_report_unit(owner, repo, sha, version, unit).await?,
+        ("card", None) => state.db.get_report(owner, repo, sha, version).await?,
+        _ => return Err(AppError::Status(StatusCode::BAD_REQUEST)),
+    };
+    let Some(report) = report else {
         return Err(AppError::Status(StatusCode::NOT_FOUND));
     };
 
-    let scope = apply_scope(&report, &project_info, &query)?;
-    match query.mode.as_deref().unwrap_or("report").to_ascii_lowercase().as_str() {
-        "shield" => mode_shield(&scope, query, &acceptable),
-        "report" => mode_report(&scope, &state, uri, query, start, &acceptable).await,
-        _ => Err(AppError::Status(StatusCode::BAD_REQUEST)),
+    let scope = apply_scope(&report, &project_info, &query, view, treemap_options.colors)?;
+    if !scope_found(&scope, &query) {
+        return Err(AppError::Status(StatusCode::NOT_FOUND));
+    }
+    let response = match mode.as_str() {
+        "shield" => mode_shield(&scope, &state, query, &acceptable).await?,
+        "bar" => mode_bar(&scope, &state, &query, treemap_options, &acceptable).await?,
+        "report" => {
+            mode_report(&scope, &state, uri, query, treemap_options, start, &acceptable).await?
+        }
+        "units" => mode_units(&scope, &state, uri, query, start, &acceptable)?,
+        "recommendations" => {
+            recommend::mode_recommendations(
+                &state,
+                scope.report,
+                scope.project_info,
+                scope.current_category,
+                scope.current_unit,
+                query.limit,
+                uri,
+                start,
+                &acceptable,
+            )
+            .await?
+        }
+        "treemap" => mode_treemap(&scope, &state, &acceptable).await?,
+        "card" => mode_card(&scope, &state, &acceptable).await?,
+        _ => return Err(AppError::Status(StatusCode::BAD_REQUEST)),
+    };
+    Ok(cache::with_cache_headers(response, policy).await?)
+}
+
+/// Whether `mode_report` will respond with the serialized report rather than rendering it.
+fn wants_report_data(acceptable: &[Mime]) -> bool {
+    for mime in acceptable {
+        if (mime.type_() == mime::STAR && mime.subtype() == mime::STAR)
+            || (mime.type_() == mime::TEXT && mime.subtype() == mime::HTML)
+            || mime.type_() == mime::IMAGE
+        {
+            return false;
+        } else if mime.type_() == mime::APPLICATION
+            && (mime.subtype() == mime::JSON || mime.subtype() == PROTOBUF)
+        {
+            return true;
+        }
     }
+    false
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -242,6 +501,7 @@ async fn mode_report(
     state: &AppState,
     uri: Uri,
     query: ReportQuery,
+    treemap_options: treemap::TreemapOptions,
     start: Instant,
     acceptable: &[Mime],
 ) -> Result<Response, AppError> {
@@ -259,7 +519,7 @@ async fn mode_report(
             return Ok(Protobuf(scope.report.report.clone()).into_response());
         } else if mime.type_() == mime::IMAGE && mime.subtype() == mime::SVG {
             let (w, h) = query.size();
-            let svg = treemap::render_svg(&scope.units, w, h, state)?;
+            let svg = treemap::render_svg(&scope.units, w, h, &treemap_options, None, state)?;
             return Ok(([(header::CONTENT_TYPE, mime::IMAGE_SVG.as_ref())], svg).into_response());
         } else if mime.type_() == mime::IMAGE {
             let format = if mime.subtype() == mime::STAR {
@@ -270,28 +530,37 @@ async fn mode_report(
                     .ok_or_else(|| AppError::Status(StatusCode::NOT_ACCEPTABLE))?
             };
             let (w, h) = query.size();
-            let data = treemap::render_image(&scope.units, w, h, state, format)?;
+            let data =
+                treemap::render_image(&scope.units, w, h, &treemap_options, state, format).await?;
             return Ok(([(header::CONTENT_TYPE, format.to_mime_type())], data).into_response());
         }
     }
     Err(AppError::Status(StatusCode::NOT_ACCEPTABLE))
 }
 
-fn mode_shield(
-    Scope { report, measures, label, .. }: &Scope<'_>,
+async fn mode_shield(
+    scope: &Scope<'_>,
+    state: &AppState,
     query: ReportQuery,
     acceptable: &[Mime],
 ) -> Result<Response, AppError> {
+    let Scope { report, measures, label, .. } = scope;
     let label = label.unwrap_or_else(|| report.project.short_name());
+    let since = query.shield.since().map_err(|_| AppError::Status(StatusCode::BAD_REQUEST))?;
+    let baseline = match since {
+        Some(since) => Some(shield_baseline(scope, state, &query, since).await?),
+        None => None,
+    };
+    let baseline = baseline.as_ref();
     for mime in acceptable {
         if (mime.type_() == mime::STAR && mime.subtype() == mime::STAR)
             || (mime.type_() == mime::IMAGE && mime.subtype() == mime::SVG)
             || (mime.type_() == mime::TEXT && mime.subtype() == mime::HTML)
         {
-            let data = badge::render_svg(measures, label, &query.shield)?;
+            let data = badge::render_svg(measures, baseline, label, &query.shield)?;
             return Ok(([(header::CONTENT_TYPE, mime::IMAGE_SVG.as_ref())], data).into_response());
         } else if mime.type_() == mime::APPLICATION && mime.subtype() == mime::JSON {
-            let data = badge::render(measures, label, &query.shield)?;
+            let data = badge::render(measures, baseline, label, &query.shield)?;
             return Ok(Json(data).into_response());
         } else if mime.type_() == mime::IMAGE {
             let format = if mime.subtype() == mime::STAR {
@@ -301,13 +570,649 @@ fn mode_shield(
                 ImageFormat::from_mime_type(mime.essence_str())
                     .ok_or_else(|| AppError::Status(StatusCode::NOT_ACCEPTABLE))?
             };
-            let data = badge::render_image(measures, label, &query.shield, format)?;
+            let data = badge::render_image(
+                measures,
+                baseline,
+                label,
+                &query.shield,
+                &state.blocking,
+                format,
+            )
+            .await?;
             return Ok(([(header::CONTENT_TYPE, format.to_mime_type())], data).into_response());
         }
     }
     Err(AppError::Status(StatusCode::NOT_ACCEPTABLE))
 }
 
+/// Renders stacked progress bars for the scope's code and data.
+async fn mode_bar(
+    scope: &Scope<'_>,
+    state: &AppState,
+    query: &ReportQuery,
+    treemap_options: treemap::TreemapOptions,
+    acceptable: &[Mime],
+) -> Result<Response, AppError> {
+    let Scope { report, measures, current_category, current_unit, current_path, .. } = scope;
+    // Category bars only apply to the whole report
+    let categories = match (current_category, current_unit, current_path) {
+        (None, None, None) => report.report.categories.as_slice(),
+        _ => &[],
+    };
+    let options = bar::BarOptions {
+        w: query.w.unwrap_or(bar::DEFAULT_BAR_WIDTH),
+        h: query.h,
+        theme: treemap_options.theme,
+        categories: query.categories.unwrap_or(false),
+    };
+    for mime in acceptable {
+        if (mime.type_() == mime::STAR && mime.subtype() == mime::STAR)
+            || (mime.type_() == mime::IMAGE && mime.subtype() == mime::SVG)
+        {
+            let svg = bar::render_svg(measures, categories, &options, state)?;
+            return Ok(([(header::CONTENT_TYPE, mime::IMAGE_SVG.as_ref())], svg).into_response());
+        } else if mime.type_() == mime::IMAGE {
+            let format = if mime.subtype() == mime::STAR {
+                // Default to PNG
+                ImageFormat::Png
+            } else {
+                ImageFormat::from_mime_type(mime.essence_str())
+                    .ok_or_else(|| AppError::Status(StatusCode::NOT_ACCEPTABLE))?
+            };
+            let data = bar::render_image(measures, categories, &options, state, format).await?;
+            return Ok(([(header::CONTENT_TYPE, format.to_mime_type())], data).into_response());
+        }
+    }
+    Err(AppError::Status(StatusCode::NOT_ACCEPTABLE))
+}
+
+/// Loads the scope's measures at a shield's baseline. If the history doesn't go back far
+/// enough, the first report is used instead. A unit, function or path that didn't exist yet
+/// has empty measures.
+async fn shield_baseline(
+    scope: &Scope<'_>,
+    state: &AppState,
+    query: &ReportQuery,
+    since: badge::Since<'_>,
+) -> Result<Measures, AppError> {
+    let Scope { report, project_info, view, colors, .. } = scope;
+    let sha = match since {
+        badge::Since::Days(days) => {
+            let commits =
+                state.db.get_version_commits(project_info.project.id, &report.version).await?;
+            let cutoff = report
+                .commit
+                .timestamp
+                .checked_sub_signed(TimeDelta::days(days as i64))
+                .ok_or(AppError::Status(StatusCode::BAD_REQUEST))?;
+            let index = commits.partition_point(|c| c.timestamp <= cutoff);
+            let Some(commit) = commits.get(index.saturating_sub(1)) else {
+                return Ok(EMPTY_MEASURES);
+            };
+            commit.sha.clone()
+        }
+        badge::Since::Commit(sha) => sha.to_ascii_lowercase(),
+    };
+    let (owner, repo, version) = (&report.project.owner, &report.project.repo, &report.version);
+    let baseline = match query.unit.as_deref() {
+        Some(unit) => state.db.get_report_unit(owner, repo, &sha, version, unit).await?,
+        None if query.path.is_none() => {
+            state.db.get_report_header(owner, repo, &sha, version).await?
+        }
+        None => state.db.get_report(owner, repo, &sha, version).await?,
+    };
+    let Some(baseline) = baseline else {
+        return Err(AppError::Status(StatusCode::NOT_FOUND));
+    };
+    let baseline_scope = apply_scope(&baseline, project_info, query, *view, *colors)?;
+    if !scope_found(&baseline_scope, query) {
+        return Ok(EMPTY_MEASURES);
+    }
+    Ok(baseline_scope.measures)
+}
+
+/// Renders a social preview card with the scope's measures, treemap and progress history.
+async fn mode_card(
+    scope: &Scope<'_>,
+    state: &AppState,
+    acceptable: &[Mime],
+) -> Result<Response, AppError> {
+    let Scope { report, project_info, measures, current_category, units, label, .. } = scope;
+    // The sparkline always shows the overall (or category) progress
+    let history = state
+        .db
+        .get_measures_history(
+            project_info.project.id,
+            &report.version,
+            current_category.map(|c| c.id.as_str()),
+        )
+        .await?
+        .into_iter()
+        .filter(|(commit, _)| commit.timestamp <= report.commit.timestamp)
+        .collect::<Vec<_>>();
+    let title = match label {
+        Some(label) => format!("{} ({})", project_info.project.name(), label),
+        None => project_info.project.name().into_owned(),
+    };
+    let subtitle = format!(
+        "{} • {} • {}",
+        report.version,
+        &report.commit.sha[..7.min(report.commit.sha.len())],
+        report.commit.timestamp.format("%Y-%m-%d")
+    );
+    let card = card::Card {
+        title: &title,
+        subtitle: &subtitle,
+        platform: project_info.project.platform.as_deref(),
+        measures,
+        units,
+        history: &history,
+    };
+    for mime in acceptable {
+        if mime.type_() == mime::IMAGE && mime.subtype() == mime::SVG {
+            let svg = card::render_svg(&card, state)?;
+            return Ok(([(header::CONTENT_TYPE, mime::IMAGE_SVG.as_ref())], svg).into_response());
+        } else if (mime.type_() == mime::STAR && mime.subtype() == mime::STAR)
+            || mime.type_() == mime::IMAGE
+        {
+            let format = if mime.subtype() == mime::STAR {
+                // Default to PNG
+                ImageFormat::Png
+            } else {
+                ImageFormat::from_mime_type(mime.essence_str())
+                    .ok_or_else(|| AppError::Status(StatusCode::NOT_ACCEPTABLE))?
+            };
+            let data = card::render_image(&card, state, format).await?;
+            return Ok(([(header::CONTENT_TYPE, format.to_mime_type())], data).into_response());
+        }
+    }
+    Err(AppError::Status(StatusCode::NOT_ACCEPTABLE))
+}
+
+/// Renders the treemap at commits sampled across the version's history as an animation.
+async fn mode_timelapse(
+    state: &AppState,
+    project_info: &ProjectInfo,
+    version: &str,
+    query: &ReportQuery,
+    view: TreemapView,
+    treemap_options: treemap::TreemapOptions,
+    acceptable: &[Mime],
+) -> Result<Response, AppError> {
+    // Skip image types that can't be animated, since browsers list several
+    let format = acceptable
+        .iter()
+        .find_map(|mime| {
+            if (mime.type_() == mime::STAR || mime.type_() == mime::IMAGE)
+                && mime.subtype() == mime::STAR
+            {
+                Some(svg::AnimationFormat::Gif)
+            } else if mime.type_() == mime::IMAGE {
+                svg::AnimationFormat::from_mime_type(mime.essence_str())
+            } else {
+                None
+            }
+        })
+        .ok_or(AppError::Status(StatusCode::NOT_ACCEPTABLE))?;
+    let from = match query.from.as_deref() {
+        Some(value) => {
+            Some(parse_date(value, false).ok_or(AppError::Status(StatusCode::BAD_REQUEST))?)
+        }
+        None => None,
+    };
+    let to = match query.to.as_deref() {
+        Some(value) => {
+            Some(parse_date(value, true).ok_or(AppError::Status(StatusCode::BAD_REQUEST))?)
+        }
+        None => None,
+    };
+    let interval = match query.interval.as_deref() {
+        None => None,
+        Some(i) if i.eq_ignore_ascii_case("day") => Some(TimeDelta::days(1)),
+        Some(i) if i.eq_ignore_ascii_case("week") => Some(TimeDelta::weeks(1)),
+        Some(i) if i.eq_ignore_ascii_case("month") => Some(TimeDelta::days(30)),
+        Some(_) => return Err(AppError::Status(StatusCode::BAD_REQUEST)),
+    };
+    let frames = query.frames.unwrap_or(DEFAULT_TIMELAPSE_FRAMES).clamp(1, MAX_TIMELAPSE_FRAMES);
+    let delay = query.delay.unwrap_or(DEFAULT_TIMELAPSE_DELAY).clamp(20, 10_000);
+
+    // Only sample commits whose report includes the unit, so that every frame has one
+    let commits = match query.unit.as_deref() {
+        Some(unit) => state.db.get_unit_commits(project_info.project.id, version, unit).await?,
+        None => state.db.get_version_commits(project_info.project.id, version).await?,
+    };
+    let commits = commits
+        .into_iter()
+        .filter(|c| from.is_none_or(|from| c.timestamp >= from))
+        .filter(|c| to.is_none_or(|to| c.timestamp <= to))
+        .collect::<Vec<_>>();
+    let sampled = sample_commits(&commits, frames, interval);
+    if sampled.is_empty() {
+        return Err(AppError::Status(StatusCode::NOT_FOUND));
+    }
+    let (w, h) = query.size();
+    // Frames are rendered one at a time and handed to the encoder as it rasterizes them, so
+    // that only a couple of reports and frames are in memory at once
+    let frame_count = sampled.len();
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<String>(1);
+    let encode = state.blocking.run(Task::Animation, move || {
+        svg::render_animation(iter::from_fn(|| rx.blocking_recv()), frame_count, delay, format)
+    });
+    let produce = async move {
+        let Project { owner, repo, .. } = &project_info.project;
+        for commit in sampled {
+            // Timelapse reports are only used once, so keep them out of the report cache
+            let report = match query.unit.as_deref() {
+                Some(unit) => {
+                    state.db.get_report_unit(owner, repo, &commit.sha, version, unit).await?
+                }
+                None => state.db.get_report_uncached(owner, repo, &commit.sha, version).await?,
+            }
+            .with_context(|| format!("Report for commit {} not found", commit.sha))?;
+            let scope = apply_scope(&report, project_info, query, view, treemap_options.colors)?;
+            if !scope_found(&scope, query) {
+                return Err(AppError::Status(StatusCode::NOT_FOUND));
+            }
+            let (size, matched, _) = view.measures(&scope.measures);
+            let percent = if size == 0 { 0.0 } else { matched as f32 / size as f32 * 100.0 };
+            let caption = format!(
+                "{} • {} • {:.2}%",
+                scope.label.unwrap_or_else(|| project_info.project.short_name()),
+                commit.timestamp.format("%Y-%m-%d"),
+                percent
+            );
+            let svg =
+                treemap::render_svg(&scope.units, w, h, &treemap_options, Some(&caption), state)?;
+            if tx.send(svg).await.is_err() {
+                // The encoder failed, and its error is returned instead
+                break;
+            }
+        }
+        Ok::<_, AppError>(())
+    };
+    let (data, ()) = tokio::try_join!(async { encode.await.map_err(AppError::from) }, produce)?;
+    Ok(([(header::CONTENT_TYPE, format.to_mime_type())], data).into_response())
+}
+
+/// Parses a `YYYY-MM-DD` date (at the start or end of the day) or an RFC 3339 timestamp.
+fn parse_date(value: &str, end_of_day: bool) -> Option<DateTime<Utc>> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        let time =
+            if end_of_day { date.and_hms_opt(23, 59, 59) } else { date.and_hms_opt(0, 0, 0) };
+        return time.map(|t| t.and_utc());
+    }
+    DateTime::parse_from_rfc3339(value).ok().map(|d| d.to_utc())
+}
+
+/// Samples up to `frames` commits: one per `interval` if that fits, otherwise at evenly spaced
+/// dates. Each date uses the latest commit at or before it, and the last commit is always
+/// included.
+fn sample_commits(commits: &[Commit], frames: u32, interval: Option<TimeDelta>) -> Vec<&Commit> {
+    let (Some(first), Some(last)) = (commits.first(), commits.last()) else {
+        return vec![];
+    };
+    let span = last.timestamp - first.timestamp;
+    let dates = match interval {
+        Some(interval) if span.num_seconds() / interval.num_seconds() < frames as i64 => (0..)
+            .map(|i| first.timestamp + interval * i)
+            .take_while(|date| *date < last.timestamp)
+            .collect::<Vec<_>>(),
+        _ => (0..frames - 1)
+            .map(|i| first.timestamp + span / (frames - 1) as i32 * i as i32)
+            .collect::<Vec<_>>(),
+    };
+    let mut out = Vec::<&Commit>::new();
+    for date in dates.into_iter().chain(iter::once(last.timestamp)) {
+        let index = commits.partition_point(|c| c.timestamp <= date);
+        let commit = &commits[index.saturating_sub(1)];
+        if out.last().is_none_or(|c| c.sha != commit.sha) {
+            out.push(commit);
+        }
+    }
+    out
+}
+
+#[derive(Serialize, Copy, Clone)]
+struct UnitSortOption {
+    key: &'static str,
+    name: &'static str,
+    descending: bool,
+}
+
+const UNIT_SORT_OPTIONS: &[UnitSortOption] = &[
+    UnitSortOption { key: "size", name: "Size", descending: true },
+    UnitSortOption { key: "unmatched", name: "Unmatched", descending: true },
+    UnitSortOption { key: "fuzzy", name: "Fuzzy match", descending: false },
+    UnitSortOption { key: "name", name: "Name", descending: false },
+];
+
+const DEFAULT_UNITS_LIMIT: u32 = 50;
+const MAX_UNITS_LIMIT: u32 = 500;
+
+/// Query parameters used by the unit listing, removed when linking elsewhere.
+const UNIT_LIST_PARAMS: &[&str] = &["mode", "sort", "order", "complete", "q", "page", "limit"];
+
+#[derive(Serialize)]
+struct UnitList<'a> {
+    total: usize,
+    page: u32,
+    limit: u32,
+    units: Vec<UnitListItem<'a>>,
+}
+
+#[derive(Serialize)]
+struct UnitListItem<'a> {
+    id: &'a str,
+    name: &'a str,
+    /// None for sections, which can't be scoped to
+    path: Option<String>,
+    size: u64,
+    matched_size: u64,
+    unmatched_size: u64,
+    fuzzy_match_percent: f32,
+    complete: bool,
+}
+
+#[derive(Serialize)]
+struct UnitsTemplateContext<'a> {
+    project_short_name: &'a str,
+    project_path: &'a str,
+    version: &'a str,
+    commit: &'a str,
+    report_path: &'a str,
+    current_unit: Option<&'a str>,
+    list: &'a UnitList<'a>,
+    sort_options: &'a [UnitSortLink],
+    current_sort: UnitSortOption,
+    descending: bool,
+    categories: &'a [ReportCategoryItem<'a>],
+    current_category: &'a ReportCategoryItem<'a>,
+    query: &'a str,
+    complete: Option<bool>,
+    prev_page_path: Option<&'a str>,
+    next_page_path: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct UnitSortLink {
+    key: &'static str,
+    name: &'static str,
+    path: String,
+}
+
+fn mode_units<'a>(
+    scope: &Scope<'a>,
+    state: &AppState,
+    uri: Uri,
+    query: ReportQuery,
+    start: Instant,
+    acceptable: &[Mime],
+) -> Result<Response, AppError> {
+    let Scope { report, project_info, current_category, current_unit, view, units, .. } = scope;
+    let current_sort = UNIT_SORT_OPTIONS
+        .iter()
+        .find(|o| o.key.eq_ignore_ascii_case(query.sort.as_deref().unwrap_or("size")))
+        .copied()
+        .ok_or(AppError::Status(StatusCode::BAD_REQUEST))?;
+    let descending = match query.order.as_deref() {
+        None => current_sort.descending,
+        Some(order) if order.eq_ignore_ascii_case("desc") => true,
+        Some(order) if order.eq_ignore_ascii_case("asc") => false,
+        Some(_) => return Err(AppError::Status(StatusCode::BAD_REQUEST)),
+    };
+    let needle = query.q.as_deref().map(str::to_lowercase).filter(|q| !q.is_empty());
+    let mut filtered = units
+        .iter()
+        .filter(|u| query.complete.is_none_or(|complete| u.complete == complete))
+        .filter(|u| {
+            needle.as_ref().is_none_or(|needle| {
+                u.name.to_lowercase().contains(needle) || u.id.to_lowercase().contains(needle)
+            })
+        })
+        .collect::<Vec<_>>();
+    match current_sort.key {
+        "size" => filtered.sort_by_key(|u| u.size),
+        "unmatched" => filtered.sort_by_key(|u| u.size.saturating_sub(u.matched_size)),
+        "fuzzy" => filtered.sort_by(|a, b| {
+            a.fuzzy_match_percent
+                .partial_cmp(&b.fuzzy_match_percent)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        }),
+        "name" => filtered.sort_by(|a, b| a.name.cmp(&b.name)),
+        _ => return Err(AppError::Status(StatusCode::BAD_REQUEST)),
+    }
+    if descending {
+        filtered.reverse();
+    }
+
+    let request_url = Url::parse(&uri.to_string()).context("Failed to parse URI")?;
+    let canonical_url = request_url.with_path(&format!(
+        "/{}/{}/{}/{}",
+        project_info.project.owner, project_info.project.repo, report.version, report.commit.sha
+    ));
+    let report_url = UNIT_LIST_PARAMS
+        .iter()
+        .fold(canonical_url.clone(), |url, param| url.query_param(param, None));
+    let limit = query.limit.unwrap_or(DEFAULT_UNITS_LIMIT).clamp(1, MAX_UNITS_LIMIT);
+    let page = query.page.unwrap_or(1).max(1);
+    let list = UnitList {
+        total: filtered.len(),
+        page,
+        limit,
+        units: filtered
+            .into_iter()
+            .skip((page - 1) as usize * limit as usize)
+            .take(limit as usize)
+            .map(|u| {
+                let param = match (u.directory, current_unit, view) {
+                    (true, _, _) => Some("path"),
+                    (false, None, _) => Some("unit"),
+                    (false, Some(_), TreemapView::Code) => Some("function"),
+                    // Sections have no scope of their own
+                    (false, Some(_), TreemapView::Data) => None,
+                };
+                UnitListItem {
+                    id: &u.id,
+                    name: &u.name,
+                    path: param.map(|param| {
+                        report_url.query_param(param, Some(&u.id)).path_and_query().to_string()
+                    }),
+                    size: u.size,
+                    matched_size: u.matched_size,
+                    unmatched_size: u.size.saturating_sub(u.matched_size),
+                    fuzzy_match_percent: u.fuzzy_match_percent,
+                    complete: u.complete,
+                }
+            })
+            .collect(),
+    };
+
+    for mime in acceptable {
+        if (mime.type_() == mime::STAR && mime.subtype() == mime::STAR)
+            || (mime.type_() == mime::TEXT && mime.subtype() == mime::HTML)
+        {
+            let sort_options = UNIT_SORT_OPTIONS
+                .iter()
+                .map(|o| {
+                    // Selecting the current sort again flips the order
+                    let order = if o.key == current_sort.key { !descending } else { o.descending };
+                    let url = canonical_url
+                        .query_param("sort", Some(o.key))
+                        .query_param("order", Some(if order { "desc" } else { "asc" }))
+                        .query_param("page", None);
+                    UnitSortLink {
+                        key: o.key,
+                        name: o.name,
+                        path: url.path_and_query().to_string(),
+                    }
+                })
+                .collect::<Vec<_>>();
+            let category_link = |id: &'a str, name: &'a str, value: Option<&str>| {
+                let url = canonical_url.query_param("category", value).query_param("page", None);
+                ReportCategoryItem { id, name, path: url.path_and_query().to_string() }
+            };
+            let all_category = category_link("all", "All", None);
+            let current_category = current_category
+                .map(|c| category_link(&c.id, &c.name, Some(&c.id)))
+                .unwrap_or_else(|| all_category.clone());
+            let categories = iter::once(all_category)
+                .chain(
+                    report
+                        .report
+                        .categories
+                        .iter()
+                        .map(|c| category_link(&c.id, &c.name, Some(&c.id))),
+                )
+                .collect::<Vec<_>>();
+            let page_path = |page: u32| {
+                canonical_url
+                    .query_param("page", Some(&page.to_string()))
+                    .path_and_query()
+                    .to_string()
+            };
+            let prev_page_path = (page > 1).then(|| page_path(page - 1));
+            let next_page_path =
+                ((page as usize * limit as usize) < list.total).then(|| page_path(page + 1));
+            let mut rendered = render(&state.templates, "units.html", UnitsTemplateContext {
+                project_short_name: project_info.project.short_name(),
+                project_path: &format!(
+                    "/{}/{}",
+                    project_info.project.owner, project_info.project.repo
+                ),
+                version: &report.version,
+                commit: &report.commit.sha,
+                report_path: report_url.path_and_query(),
+                current_unit: current_unit.map(|u| u.name.as_str()),
+                list: &list,
+                sort_options: &sort_options,
+                current_sort,
+                descending,
+                categories: &categories,
+                current_category: &current_category,
+                query: query.q.as_deref().unwrap_or_default(),
+                complete: query.complete,
+                prev_page_path: prev_page_path.as_deref(),
+                next_page_path: next_page_path.as_deref(),
+            })?;
+            let elapsed = start.elapsed();
+            rendered = rendered.replace("[[time]]", &format!("{}ms", elapsed.as_millis()));
+            return Ok(Html(rendered).into_response());
+        } else if mime.type_() == mime::APPLICATION && mime.subtype() == mime::JSON {
+            return Ok(Json(list).into_response());
+        }
+    }
+    Err(AppError::Status(StatusCode::NOT_ACCEPTABLE))
+}
+
+#[derive(Serialize)]
+struct TreemapData<'a> {
+    label: Option<&'a str>,
+    view: TreemapView,
+    units: Vec<TreemapNode<'a>>,
+}
+
+/// Node in the interactive treemap, laid out like the server-rendered treemap. Only the level
+/// being viewed (plus directory previews) is sent; the client requests deeper levels on zoom.
+#[derive(Serialize)]
+struct TreemapNode<'a> {
+    /// Unit name, mangled function name, section name or directory path
+    id: Cow<'a, str>,
+    name: Cow<'a, str>,
+    kind: TreemapNodeKind,
+    size: u64,
+    fuzzy_match_percent: f32,
+    color: &'a str,
+    complete: bool,
+    /// Whether the unit (or any unit in the directory) changed in the recent reports
+    recently_changed: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    children: Vec<TreemapNode<'a>>,
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+}
+
+#[derive(Serialize, Copy, Clone, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum TreemapNodeKind {
+    Directory,
+    Unit,
+    Function,
+    Section,
+}
+
+/// Responds with the treemap items of the scope for the interactive client.
+async fn mode_treemap(
+    scope: &Scope<'_>,
+    state: &AppState,
+    acceptable: &[Mime],
+) -> Result<Response, AppError> {
+    if !acceptable.iter().any(|mime| {
+        (mime.type_() == mime::STAR && mime.subtype() == mime::STAR)
+            || (mime.type_() == mime::APPLICATION && mime.subtype() == mime::JSON)
+    }) {
+        return Err(AppError::Status(StatusCode::NOT_ACCEPTABLE));
+    }
+    let Scope { report, project_info, current_category, current_unit, view, units, .. } = scope;
+    let recently_changed = state
+        .db
+        .get_recently_changed_units(
+            project_info.project.id,
+            &report.version,
+            report.commit.timestamp,
+            recommend::RECENT_REPORTS,
+        )
+        .await?;
+    let category_id = current_category.map(|c| c.id.as_str());
+    let item_kind = match (current_unit, view) {
+        (Some(_), TreemapView::Code) => TreemapNodeKind::Function,
+        (Some(_), TreemapView::Data) => TreemapNodeKind::Section,
+        (None, _) => TreemapNodeKind::Unit,
+    };
+    // Functions and sections inherit the unit's flag, since changes aren't tracked per function
+    let unit_changed = current_unit.is_some_and(|u| recently_changed.contains(&u.name));
+    let is_changed = |item: &ReportTemplateUnit| {
+        if item.directory {
+            let path = item.id.split('/').collect::<Vec<_>>();
+            report.report.units.iter().any(|unit| {
+                recently_changed.contains(&unit.name)
+                    && in_category(unit, category_id)
+                    && in_path(unit, &path)
+            })
+        } else if current_unit.is_some() {
+            unit_changed
+        } else {
+            recently_changed.contains(item.id.as_ref())
+        }
+    };
+    let units =
+        units.iter().map(|item| treemap_node(item, item_kind, &is_changed)).collect::<Vec<_>>();
+    Ok(Json(TreemapData { label: scope.label, view: *view, units }).into_response())
+}
+
+fn treemap_node<'a>(
+    item: &'a ReportTemplateUnit<'a>,
+    kind: TreemapNodeKind,
+    is_changed: &impl Fn(&ReportTemplateUnit) -> bool,
+) -> TreemapNode<'a> {
+    TreemapNode {
+        id: Cow::Borrowed(&item.id),
+        name: Cow::Borrowed(&item.name),
+        kind: if item.directory { TreemapNodeKind::Directory } else { kind },
+        size: item.size,
+        fuzzy_match_percent: item.fuzzy_match_percent,
+        color: &item.color,
+        complete: item.complete,
+        recently_changed: is_changed(item),
+        children: item.children.iter().map(|c| treemap_node(c, kind, is_changed)).collect(),
+        x: item.x,
+        y: item.y,
+        w: item.w,
+        h: item.h,
+    }
+}
+
 const EMPTY_MEASURES: Measures = Measures {
     fuzzy_match_percent: 0.0,
     total_code: 0,
@@ -330,25 +1235,38 @@ const EMPTY_MEASURES: Measures = Measures {
 struct Scope<'a> {
     report: &'a ReportFile,
     project_info: &'a ProjectInfo,
-    measures: &'a Measures,
+    measures: Measures,
     current_category: Option<&'a ReportCategory>,
     current_unit: Option<&'a ReportUnit>,
+    current_function: Option<&'a ReportItem>,
+    current_path: Option<Vec<&'a str>>,
+    view: TreemapView,
+    colors: TreemapColors,
     units: Vec<ReportTemplateUnit<'a>>,
     label: Option<&'a str>,
 }
 
+/// Whether the requested unit, function and path all exist in the scope's report.
+fn scope_found(scope: &Scope, query: &ReportQuery) -> bool {
+    !((query.unit.is_some() && scope.current_unit.is_none())
+        || (query.function.is_some() && scope.current_function.is_none())
+        || (query.unit.is_none() && query.path.is_some() && scope.current_path.is_none()))
+}
+
 fn apply_scope<'a>(
     report: &'a ReportFile,
     project_info: &'a ProjectInfo,
     query: &ReportQuery,
+    view: TreemapView,
+    colors: TreemapColors,
 ) -> Result<Scope<'a>> {
-    let mut measures = report.report.measures.as_ref().unwrap_or(&EMPTY_MEASURES);
+    let mut measures = *report.report.measures.as_ref().unwrap_or(&EMPTY_MEASURES);
     let mut current_category = None;
     let mut category_id_filter = None;
     if let Some(category) =
         query.category.as_ref().and_then(|id| report.report.categories.iter().find(|c| c.id == *id))
     {
-        measures = category.measures.as_ref().unwrap_or(&EMPTY_MEASURES);
+        measures = *category.measures.as_ref().unwrap_or(&EMPTY_MEASURES);
         current_category = Some(category);
         category_id_filter = Some(category.id.clone());
     }
@@ -358,68 +1276,105 @@ fn apply_scope<'a>(
         .as_ref()
         .and_then(|unit_name| report.report.units.iter().find(|u| u.name == *unit_name))
     {
-        measures = unit.measures.as_ref().unwrap_or(&EMPTY_MEASURES);
+        measures = *unit.measures.as_ref().unwrap_or(&EMPTY_MEASURES);
         current_unit = Some(unit);
     }
+    let mut current_function = None;
+    let mut current_path_units = vec![];
+    if let Some(function) = current_unit
+        .zip(query.function.as_ref())
+        .and_then(|(unit, name)| unit.functions.iter().find(|f| f.name == *name))
+    {
+        measures = function_measures(function);
+        current_function = Some(function);
+    }
+    let mut current_path = None;
+    if let (None, Some(path)) = (current_unit, query.path.as_deref()) {
+        let path = path.split('/').filter(|c| !c.is_empty()).collect::<Vec<_>>();
+        let path_units = report
+            .report
+            .units
+            .iter()
+            .filter(|unit| in_category(unit, category_id_filter.as_deref()))
+            .filter(|unit| in_path(unit, &path))
+            .collect::<Vec<_>>();
+        if let Some(first) = path_units.first() {
+            if !path.is_empty() {
+                measures = path_units.iter().filter_map(|u| u.measures).collect();
+            }
+            // Borrow the components from the report rather than the query
+            current_path = Some(unit_path_components(first).take(path.len()).collect::<Vec<_>>());
+            current_path_units = path_units;
+        } else if path.is_empty() {
+            current_path = Some(vec![]);
+        }
+    }
     let (w, h) = query.size();
-    let mut units =
-        if let Some(unit) = current_unit {
-            unit.functions
-                .iter()
-                .filter_map(|f| {
-                    if f.size == 0 {
-                        return None;
-                    }
-                    Some(ReportTemplateUnit {
-                        name: f
-                            .metadata
+    let mut units = if let (Some(unit), TreemapView::Data) = (current_unit, view) {
+        unit.sections
+            .iter()
+            .filter(|s| s.size > 0 && !is_code_section(&s.name))
+            .map(|s| ReportTemplateUnit {
+                id: Cow::Borrowed(&s.name),
+                name: Cow::Borrowed(&s.name),
+                size: s.size,
+                matched_size: if s.fuzzy_match_percent == 100.0 { s.size } else { 0 },
+                complete: s.fuzzy_match_percent == 100.0,
+                fuzzy_match_percent: s.fuzzy_match_percent,
+                color: colors.color(s.fuzzy_match_percent, s.fuzzy_match_percent == 100.0),
+                directory: false,
+                children: vec![],
+                x: 0.0,
+                y: 0.0,
+                w: 0.0,
+                h: 0.0,
+            })
+            .collect::<Vec<_>>()
+    } else if let Some(unit) = current_unit {
+        unit.functions
+            .iter()
+            .filter_map(|f| {
+                if f.size == 0 {
+                    return None;
+                }
+                Some(ReportTemplateUnit {
+                    id: Cow::Borrowed(&f.name),
+                    name: Cow::Borrowed(
+                        f.metadata
                             .as_ref()
                             .and_then(|m| m.demangled_name.as_deref())
                             .unwrap_or(&f.name),
-                        total_code: f.size,
-                        fuzzy_match_percent: f.fuzzy_match_percent,
-                        color: treemap::unit_color(f.fuzzy_match_percent),
-                        x: 0.0,
-                        y: 0.0,
-                        w: 0.0,
-                        h: 0.0,
-                    })
+                    ),
+                    size: f.size,
+                    matched_size: if f.fuzzy_match_percent == 100.0 { f.size } else { 0 },
+                    complete: f.fuzzy_match_percent == 100.0,
+                    fuzzy_match_percent: f.fuzzy_match_percent,
+                    color: colors.color(f.fuzzy_match_percent, f.fuzzy_match_percent == 100.0),
+                    directory: false,
+                    children: vec![],
+                    x: 0.0,
+                    y: 0.0,
+                    w: 0.0,
+                    h: 0.0,
                 })
-                .collect::<Vec<_>>()
-        } else {
-            report
-                .report
-                .units
-                .iter()
-                .filter_map(|unit| {
-                    if let Some(category_id) = &category_id_filter {
-                        if !unit.metadata.as_ref().map_or(false, |m| {
-                            m.progress_categories.iter().any(|c| c == category_id)
-                        }) {
-                            return None;
-                        }
-                    }
-                    let measures = unit.measures.as_ref()?;
-                    if measures.total_code == 0 {
-                        return None;
-                    }
-                    Some(ReportTemplateUnit {
-                        name: &unit.name,
-                        total_code: measures.total_code,
-                        fuzzy_match_percent: measures.fuzzy_match_percent,
-                        color: treemap::unit_color(measures.fuzzy_match_percent),
-                        x: 0.0,
-                        y: 0.0,
-                        w: 0.0,
-                        h: 0.0,
-                    })
-                })
-                .collect::<Vec<_>>()
-        };
+            })
+            .collect::<Vec<_>>()
+    } else if let Some(path) = &current_path {
+        hierarchy_items(&current_path_units, path, view, colors, true)
+    } else {
+        report
+            .report
+            .units
+            .iter()
+            .filter(|unit| in_category(unit, category_id_filter.as_deref()))
+            .filter_map(|unit| unit_item(unit, Cow::Borrowed(&unit.name), view, colors))
+            .collect::<Vec<_>>()
+    };
+    let aspect = w as f32 / h as f32;
     treemap::layout_units(
         &mut units,
-        w as f32 / h as f32,
-        |i| i.total_code as f32,
+        aspect,
+        |i| i.size as f32,
         |i, r| {
             i.x = r.x;
             i.y = r.y;
@@ -427,16 +1382,275 @@ fn apply_scope<'a>(
             i.h = r.h;
         },
     );
-    let label = current_unit
-        .as_ref()
-        .map(|u| u.name.rsplit_once('/').map_or(u.name.as_str(), |(_, name)| name))
+    for parent in &mut units {
+        layout_children(parent, aspect);
+    }
+    let label = current_function
+        .map(|f| f.metadata.as_ref().and_then(|m| m.demangled_name.as_deref()).unwrap_or(&f.name))
+        .or_else(|| {
+            current_unit
+                .as_ref()
+                .map(|u| u.name.rsplit_once('/').map_or(u.name.as_str(), |(_, name)| name))
+        })
+        .or_else(|| current_path.as_ref().and_then(|p| p.last().copied()))
         .or_else(|| current_category.as_ref().map(|c| c.name.as_str()));
-    Ok(Scope { report, project_info, measures, current_category, current_unit, units, label })
+    Ok(Scope {
+        report,
+        project_info,
+        measures,
+        current_category,
+        current_unit,
+        current_function,
+        current_path,
+        view,
+        colors,
+        units,
+        label,
+    })
+}
+
+#[derive(Serialize, Copy, Clone, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum TreemapView {
+    Code,
+    Data,
+}
+
+impl TreemapView {
+    fn from_query(view: Option<&str>) -> Option<Self> {
+        match view {
+            None => Some(Self::Code),
+            Some(v) if v.eq_ignore_ascii_case("code") => Some(Self::Code),
+            Some(v) if v.eq_ignore_ascii_case("data") => Some(Self::Data),
+            Some(_) => None,
+        }
+    }
+
+    /// Returns the total size, matched size and match percent shown in this view.
+    fn measures(self, measures: &Measures) -> (u64, u64, f32) {
+        match self {
+            Self::Code => {
+                (measures.total_code, measures.matched_code, measures.fuzzy_match_percent)
+            }
+            Self::Data => {
+                (measures.total_data, measures.matched_data, measures.matched_data_percent)
+            }
+        }
+    }
+}
+
+fn is_code_section(name: &str) -> bool {
+    name.starts_with(".text") || name == ".init" || name == ".fini"
+}
+
+fn in_category(unit: &ReportUnit, category_id: Option<&str>) -> bool {
+    category_id.is_none_or(|id| {
+        unit.metadata.as_ref().is_some_and(|m| m.progress_categories.iter().any(|c| c == id))
+    })
+}
+
+fn unit_complete(unit: &ReportUnit) -> bool {
+    unit.metadata.as_ref().and_then(|m| m.complete).unwrap_or(false)
+}
+
+fn unit_item<'a>(
+    unit: &'a ReportUnit,
+    name: Cow<'a, str>,
+    view: TreemapView,
+    colors: TreemapColors,
+) -> Option<ReportTemplateUnit<'a>> {
+    let (size, matched_size, percent) = view.measures(unit.measures.as_ref()?);
+    if size == 0 {
+        return None;
+    }
+    let complete = unit_complete(unit);
+    Some(ReportTemplateUnit {
+        id: Cow::Borrowed(&unit.name),
+        name,
+        size,
+        matched_size,
+        complete,
+        fuzzy_match_percent: percent,
+        color: colors.color(percent, complete),
+        directory: false,
+        children: vec![],
+        x: 0.0,
+        y: 0.0,
+        w: 0.0,
+        h: 0.0,
+    })
+}
+
+/// Path components of a unit in the directory hierarchy. Units in REL/overlay modules are
+/// grouped under their module name.
+fn unit_path_components(unit: &ReportUnit) -> impl Iterator<Item = &str> {
+    let module_name = unit
+        .metadata
+        .as_ref()
+        .filter(|m| m.module_id.is_some_and(|id| id != 0))
+        .and_then(|m| m.module_name.as_deref());
+    module_name.into_iter().chain(unit.name.split('/'))
+}
+
+/// Whether the unit is (possibly indirectly) inside the directory `path`.
+fn in_path(unit: &ReportUnit, path: &[&str]) -> bool {
+    let mut components = unit_path_components(unit);
+    components.by_ref().take(path.len()).eq(path.iter().copied()) && components.next().is_some()
+}
+
+/// Groups units by their next path component below `path`, producing items for the units
+/// directly inside it and for each subdirectory with aggregated measures. If `nested`, each
+/// subdirectory also contains items for its own contents.
+fn hierarchy_items<'a>(
+    units: &[&'a ReportUnit],
+    path: &[&str],
+    view: TreemapView,
+    colors: TreemapColors,
+    nested: bool,
+) -> Vec<ReportTemplateUnit<'a>> {
+    let mut items = vec![];
+    let mut directories = BTreeMap::<&'a str, Vec<&'a ReportUnit>>::new();
+    for &unit in units {
+        let mut components = unit_path_components(unit).skip(path.len());
+        let Some(component) = components.next() else {
+            continue;
+        };
+        if components.next().is_none() {
+            items.extend(unit_item(unit, Cow::Borrowed(component), view, colors));
+        } else {
+            directories.entry(component).or_default().push(unit);
+        }
+    }
+    for (component, group) in directories {
+        let measures = group.iter().filter_map(|u| u.measures).collect::<Measures>();
+        let (size, matched_size, percent) = view.measures(&measures);
+        if size == 0 {
+            continue;
+        }
+        let directory_path = path.iter().copied().chain(iter::once(component)).collect::<Vec<_>>();
+        let children = if nested {
+            hierarchy_items(&group, &directory_path, view, colors, false)
+        } else {
+            vec![]
+        };
+        let complete = group.iter().all(|u| unit_complete(u));
+        items.push(ReportTemplateUnit {
+            id: Cow::Owned(directory_path.join("/")),
+            name: Cow::Owned(format!("{}/", component)),
+            size,
+            matched_size,
+            complete,
+            fuzzy_match_percent: percent,
+            color: colors.color(percent, complete),
+            directory: true,
+            children,
+            x: 0.0,
+            y: 0.0,
+            w: 0.0,
+            h: 0.0,
+        });
+    }
+    items.sort_by(|a, b| a.id.cmp(&b.id));
+    items
+}
+
+/// Lays out a directory's children inside its (inset) bounds.
+fn layout_children(parent: &mut ReportTemplateUnit, aspect: f32) {
+    if parent.children.is_empty() {
+        return;
+    }
+    let inset = parent.w.min(parent.h / aspect) * 0.05;
+    let (x, y) = (parent.x + inset, parent.y + inset * aspect);
+    let (w, h) = (parent.w - inset * 2.0, parent.h - inset * aspect * 2.0);
+    if w <= 0.0 || h <= 0.0 {
+        parent.children.clear();
+        return;
+    }
+    treemap::layout_units(
+        &mut parent.children,
+        aspect * w / h,
+        |i| i.size as f32,
+        |i, r| {
+            i.x = x + r.x * w;
+            i.y = y + r.y * h;
+            i.w = r.w * w;
+            i.h = r.h * h;
+        },
+    );
+}
+
+/// Measures for a single function, so that it can be displayed like a unit.
+fn function_measures(function: &ReportItem) -> Measures {
+    let matched = function.fuzzy_match_percent == 100.0;
+    let matched_percent = if matched { 100.0 } else { 0.0 };
+    Measures {
+        fuzzy_match_percent: function.fuzzy_match_percent,
+        total_code: function.size,
+        matched_code: if matched { function.size } else { 0 },
+        matched_code_percent: matched_percent,
+        total_functions: 1,
+        matched_functions: matched as u32,
+        matched_functions_percent: matched_percent,
+        ..EMPTY_MEASURES
+    }
+}
+
+/// Builds the match history of a function, keeping only the commits where it changed. Only
+/// the unit's [`MAX_FUNCTION_HISTORY`] most recent revisions are loaded.
+async fn function_history(
+    state: &AppState,
+    project_info: &ProjectInfo,
+    version: &str,
+    unit: &ReportUnit,
+    function: &ReportItem,
+    request_url: &Url,
+) -> Result<Vec<FunctionHistoryItem>> {
+    let entries = state
+        .db
+        .get_unit_history(project_info.project.id, version, &unit.name, None, MAX_FUNCTION_HISTORY)
+        .await?;
+    let mut out = Vec::<FunctionHistoryItem>::new();
+    for entry in entries {
+        let item =
+            entry.unit.as_ref().and_then(|u| u.functions.iter().find(|f| f.name == function.name));
+        let size = item.map(|f| f.size);
+        let fuzzy_match_percent = item.map(|f| f.fuzzy_match_percent);
+        if out.last().is_some_and(|last| {
+            last.size == size && last.fuzzy_match_percent == fuzzy_match_percent
+        }) {
+            continue;
+        }
+        let url = request_url.with_path(&format!(
+            "/{}/{}/{}/{}",
+            project_info.project.owner, project_info.project.repo, version, entry.commit.sha
+        ));
+        out.push(FunctionHistoryItem {
+            path: url.path_and_query().to_string(),
+            commit: entry.commit.sha,
+            timestamp: entry.commit.timestamp,
+            size,
+            fuzzy_match_percent,
+        });
+    }
+    // Most recent first
+    out.reverse();
+    Ok(out)
 }
 
 async fn render_template(scope: &Scope<'_>, state: &AppState, uri: Uri) -> Result<String> {
-    let Scope { report, project_info, measures, current_category, current_unit, units, label } =
-        scope;
+    let Scope {
+        report,
+        project_info,
+        measures,
+        current_category,
+        current_unit,
+        current_function,
+        current_path,
+        view,
+        colors: _,
+        units,
+        label,
+    } = scope;
     let commit = match state
         .github
         .get_commit(&project_info.project.owner, &project_info.project.repo, &report.commit.sha)
@@ -463,6 +1677,7 @@ async fn render_template(scope: &Scope<'_>, state: &AppState, uri: Uri) -> Resul
         project_info.project.owner, project_info.project.repo, report.version, report.commit.sha
     ));
     let image_url = canonical_url.with_path(&format!("{}.png", canonical_url.path()));
+    let card_url = image_url.query_param("mode", Some("card"));
 
     let versions = project_info
         .report_versions
@@ -516,15 +1731,86 @@ async fn render_template(scope: &Scope<'_>, state: &AppState, uri: Uri) -> Resul
         url.path_and_query().to_string()
     });
 
-    let units_path = canonical_url.query_param("unit", None).path_and_query().to_string();
+    let units_path = canonical_url
+        .query_param("unit", None)
+        .query_param("function", None)
+        .path_and_query()
+        .to_string();
+    let functions_path = canonical_url.query_param("function", None).path_and_query().to_string();
+    let path_items = current_path
+        .as_ref()
+        .map(|path| {
+            iter::once(("Units", String::new()))
+                .chain((1..=path.len()).map(|i| (path[i - 1], path[..i].join("/"))))
+                .map(|(name, path)| ReportPathItem {
+                    name,
+                    path: canonical_url
+                        .query_param("path", Some(&path))
+                        .path_and_query()
+                        .to_string(),
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let view_paths = [("code", "Code"), ("data", "Data")]
+        .into_iter()
+        .map(|(id, name)| ReportTemplateVersion {
+            id: name,
+            path: canonical_url
+                .query_param("view", (id != "code").then_some(id))
+                .query_param("function", None)
+                .path_and_query()
+                .to_string(),
+        })
+        .collect::<Vec<_>>();
+    let view_toggle_path = if current_path.is_some() {
+        canonical_url.query_param("path", None)
+    } else {
+        canonical_url.query_param("path", Some(""))
+    }
+    .path_and_query()
+    .to_string();
+    let list_path = canonical_url.query_param("mode", Some("units")).path_and_query().to_string();
+    let recommendations_path =
+        canonical_url.query_param("mode", Some("recommendations")).path_and_query().to_string();
+    let compare_path = canonical_url
+        .query_param("mode", Some("compare"))
+        .query_param("unit", None)
+        .query_param("function", None)
+        .query_param("path", None)
+        .path_and_query()
+        .to_string();
+    let treemap_data_path =
+        canonical_url.query_param("mode", Some("treemap")).path_and_query().to_string();
+    let current_function = match current_unit.zip(*current_function) {
+        Some((unit, function)) => Some(ReportTemplateFunction {
+            name: &function.name,
+            demangled_name: function.metadata.as_ref().and_then(|m| m.demangled_name.as_deref()),
+            size: function.size,
+            fuzzy_match_percent: function.fuzzy_match_percent,
+            address: function
+                .metadata
+                .as_ref()
+                .and_then(|m| m.virtual_address)
+                .map(|addr| format!("{:#010X}", addr)),
+            history: function_history(
+                state,
+                project_info,
+                &report.version,
+                unit,
+                function,
+                &request_url,
+            )
+            .await?,
+        }),
+        None => None,
+    };
     let commit_message = commit.as_ref().and_then(|c| c.message.lines().next());
     let commit_url = format!("{}/commit/{}", project_info.project.repo_url(), report.commit.sha);
     let source_file_url = current_unit
         .and_then(|u| u.metadata.as_ref())
         .and_then(|m| m.source_path.as_deref())
-        .map(|path| {
-            format!("{}/blob/{}/{}", project_info.project.repo_url(), report.commit.sha, path)
-        });
+        .map(|path| project_info.project.source_url(&report.commit.sha, path));
     let project_name = if let Some(label) = label {
         Cow::Owned(format!("{} ({})", project_info.project.name(), label))
     } else {
@@ -544,7 +1830,7 @@ async fn render_template(scope: &Scope<'_>, state: &AppState, uri: Uri) -> Resul
         project_path: &project_base_path,
         commit: &report.commit.sha,
         version: &report.version,
-        measures: TemplateMeasures::from(*measures),
+        measures: TemplateMeasures::from(measures),
         units,
         versions: &versions,
         prev_commit_path: prev_commit_path.as_deref(),
@@ -555,10 +1841,83 @@ async fn render_template(scope: &Scope<'_>, state: &AppState, uri: Uri) -> Resul
         canonical_path: canonical_url.path_and_query(),
         canonical_url: canonical_url.as_ref(),
         image_url: image_url.as_ref(),
+        card_url: card_url.as_ref(),
+        card_width: card::CARD_WIDTH,
+        card_height: card::CARD_HEIGHT,
         current_unit: current_unit.map(|u| u.name.as_str()),
+        current_function: current_function.as_ref(),
         units_path: &units_path,
+        functions_path: &functions_path,
+        current_path: current_path.as_ref().map(|p| p.join("/")),
+        path_items: &path_items,
+        view_toggle_path: &view_toggle_path,
+        view: *view,
+        view_paths: &view_paths,
+        list_path: &list_path,
+        recommendations_path: &recommendations_path,
+        compare_path: &compare_path,
+        treemap_data_path: &treemap_data_path,
         commit_message,
         commit_url: &commit_url,
         source_file_url: source_file_url.as_deref(),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    /// Commits at the given offsets in hours, named by their index.
+    fn commits(hours: &[i64]) -> Vec<Commit> {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        hours
+            .iter()
+            .enumerate()
+            .map(|(i, &h)| Commit { sha: i.to_string(), timestamp: start + TimeDelta::hours(h) })
+            .collect()
+    }
+
+    fn sampled(commits: &[Commit], frames: u32, interval: Option<TimeDelta>) -> Vec<&str> {
+        sample_commits(commits, frames, interval).into_iter().map(|c| c.sha.as_str()).collect()
+    }
+
+    #[test]
+    fn samples_nothing_without_commits() {
+        assert!(sampled(&[], 10, None).is_empty());
+    }
+
+    #[test]
+    fn samples_last_commit_for_one_frame() {
+        let daily = commits(&[0, 24, 48, 72]);
+        assert_eq!(sampled(&daily, 1, None), ["3"]);
+        assert_eq!(sampled(&daily, 1, Some(TimeDelta::days(1))), ["3"]);
+        assert_eq!(sampled(&commits(&[0]), 1, None), ["0"]);
+    }
+
+    #[test]
+    fn samples_evenly_spaced_dates() {
+        let daily = commits(&(0..10).map(|d| d * 24).collect::<Vec<_>>());
+        assert_eq!(sampled(&daily, 4, None), ["0", "3", "6", "9"]);
+        // Every commit fits
+        assert_eq!(sampled(&daily, 50, None).len(), 10);
+    }
+
+    #[test]
+    fn samples_one_commit_per_interval() {
+        let daily = commits(&(0..10).map(|d| d * 24).collect::<Vec<_>>());
+        assert_eq!(sampled(&daily, 10, Some(TimeDelta::weeks(1))), ["0", "7", "9"]);
+        assert_eq!(sampled(&daily, 10, Some(TimeDelta::days(3))), ["0", "3", "6", "9"]);
+        // Too many intervals for the frame count falls back to even spacing
+        assert_eq!(sampled(&daily, 3, Some(TimeDelta::days(1))), ["0", "4", "9"]);
+    }
+
+    #[test]
+    fn samples_each_commit_once() {
+        // Dates between the clustered commits and the last one all resolve to commit 1
+        let clustered = commits(&[0, 1, 240]);
+        assert_eq!(sampled(&clustered, 5, None), ["0", "1", "2"]);
+        assert_eq!(sampled(&clustered, 10, Some(TimeDelta::days(1))), ["0", "1", "2"]);
+    }
+}
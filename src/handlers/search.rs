@@ -0,0 +1,98 @@
+use std::time::Instant;
+
+use anyhow::Context;
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use super::{parse_accept, AppError, FullUri};
+use crate::{db::MIN_QUERY_LEN, models::SearchResult, templates::render, util::UrlExt, AppState};
+
+const DEFAULT_LIMIT: u32 = 50;
+const MAX_LIMIT: u32 = 200;
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    q: Option<String>,
+    limit: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct SearchTemplateContext<'a> {
+    query: &'a str,
+    min_query_len: usize,
+    results: &'a [SearchResultContext],
+}
+
+#[derive(Serialize)]
+struct SearchResultContext {
+    #[serde(flatten)]
+    result: SearchResult,
+    project_path: String,
+    unit_path: String,
+    function_path: Option<String>,
+}
+
+pub async fn get_search(
+    Query(query): Query<SearchQuery>,
+    headers: HeaderMap,
+    FullUri(uri): FullUri,
+    State(state): State<AppState>,
+) -> Result<Response, AppError> {
+    let start = Instant::now();
+    let acceptable = parse_accept(&headers, None);
+    if acceptable.is_empty() {
+        return Err(AppError::Status(StatusCode::NOT_ACCEPTABLE));
+    }
+    let q = query.q.as_deref().map(str::trim).unwrap_or_default();
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let request_url = Url::parse(&uri.to_string()).context("Failed to parse URI")?;
+    let results = state
+        .db
+        .search(q, limit)
+        .await?
+        .into_iter()
+        .map(|result| {
+            let report_url = request_url.with_path(&format!(
+                "/{}/{}/{}/{}",
+                result.owner, result.repo, result.version, result.commit
+            ));
+            let unit_url = report_url
+                .query_param("q", None)
+                .query_param("limit", None)
+                .query_param("unit", Some(&result.unit_name));
+            let function_path = (result.kind == "function").then(|| {
+                unit_url.query_param("function", Some(&result.name)).path_and_query().to_string()
+            });
+            SearchResultContext {
+                project_path: format!("/{}/{}", result.owner, result.repo),
+                unit_path: unit_url.path_and_query().to_string(),
+                function_path,
+                result,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    for mime in acceptable {
+        if (mime.type_() == mime::STAR && mime.subtype() == mime::STAR)
+            || (mime.type_() == mime::TEXT && mime.subtype() == mime::HTML)
+        {
+            let mut rendered = render(&state.templates, "search.html", SearchTemplateContext {
+                query: q,
+                min_query_len: MIN_QUERY_LEN,
+                results: &results,
+            })?;
+            let elapsed = start.elapsed();
+            rendered = rendered.replace("[[time]]", &format!("{}ms", elapsed.as_millis()));
+            return Ok(Html(rendered).into_response());
+        } else if mime.type_() == mime::APPLICATION && mime.subtype() == mime::JSON {
+            return Ok(Json(results).into_response());
+        }
+    }
+    Err(AppError::Status(StatusCode::NOT_ACCEPTABLE))
+}
@@ -0,0 +1,7 @@
+use axum::{extract::State, response::IntoResponse, Json};
+
+use crate::AppState;
+
+pub async fn get_stats(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.db.report_cache_stats())
+}
@@ -4,7 +4,9 @@ use palette::{Mix, Srgb};
 use serde::Serialize;
 use streemap::Rect;
 
-use crate::{handlers::report::ReportTemplateUnit, svg, templates::render, AppState};
+use crate::{
+    blocking::Task, handlers::report::ReportTemplateUnit, svg, templates::render, AppState,
+};
 
 pub fn layout_units<T, S, R>(items: &mut [T], aspect: f32, size_fn: S, mut set_rect_fn: R)
 where
@@ -28,40 +30,228 @@ where
     });
 }
 
+const LABEL_FONT_SIZE: f32 = 12.0;
+const LABEL_PADDING: f32 = 4.0;
+/// Average glyph width relative to the font size, used to estimate label widths.
+const LABEL_CHAR_WIDTH: f32 = 0.6;
+const CAPTION_FONT_SIZE: f32 = 16.0;
+const CAPTION_MARGIN: f32 = 8.0;
+
+#[derive(Copy, Clone, Default, Eq, PartialEq)]
+pub enum ColorScheme {
+    /// Dark blue to green
+    #[default]
+    Default,
+    /// Perceptually uniform and colour-blind safe
+    Viridis,
+    /// Optimized for red-green colour blindness
+    Cividis,
+}
+
+impl ColorScheme {
+    pub fn from_query(scheme: Option<&str>) -> Option<Self> {
+        match scheme {
+            None => Some(Self::Default),
+            Some(s) if s.eq_ignore_ascii_case("default") => Some(Self::Default),
+            Some(s) if s.eq_ignore_ascii_case("viridis") => Some(Self::Viridis),
+            Some(s) if s.eq_ignore_ascii_case("cividis") => Some(Self::Cividis),
+            Some(_) => None,
+        }
+    }
+
+    /// Gradient stops from 0% to 100% matched.
+    fn stops(self) -> &'static [(u8, u8, u8)] {
+        match self {
+            Self::Default => &[(42, 49, 64), (0, 200, 0)],
+            Self::Viridis => {
+                &[(68, 1, 84), (59, 82, 139), (33, 145, 140), (94, 201, 98), (253, 231, 37)]
+            }
+            Self::Cividis => {
+                &[(0, 32, 77), (65, 77, 107), (124, 123, 120), (188, 175, 111), (255, 234, 70)]
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum Theme {
+    Light,
+    Dark,
+}
+
+impl Theme {
+    pub fn from_query(theme: &str) -> Option<Self> {
+        if theme.eq_ignore_ascii_case("light") {
+            Some(Self::Light)
+        } else if theme.eq_ignore_ascii_case("dark") {
+            Some(Self::Dark)
+        } else {
+            None
+        }
+    }
+
+    pub fn background(self) -> &'static str {
+        match self {
+            Self::Light => "#ffffff",
+            Self::Dark => "#13171f",
+        }
+    }
+
+    fn stroke(self) -> &'static str {
+        match self {
+            Self::Light => "#ffffff",
+            Self::Dark => "#000000",
+        }
+    }
+}
+
+/// How units are coloured.
+#[derive(Copy, Clone, Default)]
+pub struct TreemapColors {
+    pub scheme: ColorScheme,
+    /// Only distinguish complete and incomplete units, using the ends of the scheme
+    pub binary: bool,
+}
+
+impl TreemapColors {
+    pub fn color(&self, fuzzy_match_percent: f32, complete: bool) -> String {
+        let stops = self.scheme.stops();
+        let (r, g, b) = if self.binary {
+            stops[if complete { stops.len() - 1 } else { 0 }]
+        } else {
+            let t = (fuzzy_match_percent / 100.0).clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+            let i = (t as usize).min(stops.len() - 2);
+            let (r, g, b) = rgb(stops[i]).mix(rgb(stops[i + 1]), t - i as f32).into_components();
+            ((r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8)
+        };
+        format!("#{:02x}{:02x}{:02x}", r, g, b)
+    }
+}
+
+/// Options for the rendered (SVG or raster) treemap.
+#[derive(Copy, Clone, Default)]
+pub struct TreemapOptions {
+    pub colors: TreemapColors,
+    /// Draws a background and matching unit borders; transparent if None
+    pub theme: Option<Theme>,
+    /// Draws unit names inside rectangles large enough to fit them
+    pub labels: bool,
+}
+
 #[derive(Serialize)]
 struct TreemapTemplateContext<'a> {
     units: &'a [ReportTemplateUnit<'a>],
     w: u32,
     h: u32,
+    background: Option<&'static str>,
+    stroke: &'static str,
+    labels: Vec<TreemapLabel<'a>>,
+    font_size: f32,
+    caption: Option<TreemapCaption<'a>>,
+}
+
+/// Text drawn over the bottom left corner, on a translucent box.
+#[derive(Serialize)]
+struct TreemapCaption<'a> {
+    text: &'a str,
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+    font_size: f32,
+}
+
+#[derive(Serialize)]
+struct TreemapLabel<'a> {
+    text: &'a str,
+    color: &'static str,
+    x: f32,
+    y: f32,
 }
 
 pub fn render_svg(
     units: &[ReportTemplateUnit],
     w: u32,
     h: u32,
+    options: &TreemapOptions,
+    caption: Option<&str>,
     state: &AppState,
 ) -> Result<String> {
-    render(&state.templates, "treemap.svg", TreemapTemplateContext { units, w, h })
+    let labels = if options.labels { unit_labels(units, w, h) } else { vec![] };
+    let caption = caption.map(|text| {
+        let box_h = CAPTION_FONT_SIZE + LABEL_PADDING * 2.0;
+        TreemapCaption {
+            text,
+            x: CAPTION_MARGIN,
+            y: h as f32 - CAPTION_MARGIN - box_h,
+            w: text.chars().count() as f32 * CAPTION_FONT_SIZE * LABEL_CHAR_WIDTH
+                + LABEL_PADDING * 2.0,
+            h: box_h,
+            font_size: CAPTION_FONT_SIZE,
+        }
+    });
+    render(&state.templates, "treemap.svg", TreemapTemplateContext {
+        units,
+        w,
+        h,
+        background: options.theme.map(Theme::background),
+        stroke: options.theme.map_or("#000000", Theme::stroke),
+        labels,
+        font_size: LABEL_FONT_SIZE,
+        caption,
+    })
 }
 
-pub fn render_image(
-    units: &[ReportTemplateUnit],
+pub async fn render_image(
+    units: &[ReportTemplateUnit<'_>],
     w: u32,
     h: u32,
+    options: &TreemapOptions,
     state: &AppState,
     format: ImageFormat,
 ) -> Result<Vec<u8>> {
-    let svg = render_svg(units, w, h, state)?;
-    svg::render_image(&svg, format)
+    let svg = render_svg(units, w, h, options, None, state)?;
+    state.blocking.run(Task::Render, move || svg::render_image(&svg, format)).await
 }
 
-fn rgb(r: u8, g: u8, b: u8) -> Srgb {
-    Srgb::new(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0)
+/// Labels for the top-level units whose rectangles can fit their name. Labels are centered;
+/// the text width is estimated, since there's no text layout before rendering.
+fn unit_labels<'a>(units: &'a [ReportTemplateUnit<'a>], w: u32, h: u32) -> Vec<TreemapLabel<'a>> {
+    units
+        .iter()
+        .filter_map(|unit| {
+            let text = if unit.directory {
+                unit.name.as_ref()
+            } else {
+                unit.name.rsplit('/').next().unwrap_or(&unit.name)
+            };
+            let (uw, uh) = (unit.w * w as f32, unit.h * h as f32);
+            let text_width = text.chars().count() as f32 * LABEL_FONT_SIZE * LABEL_CHAR_WIDTH;
+            if text_width + LABEL_PADDING * 2.0 > uw || LABEL_FONT_SIZE + LABEL_PADDING * 2.0 > uh {
+                return None;
+            }
+            Some(TreemapLabel {
+                text,
+                color: label_color(&unit.color),
+                x: unit.x * w as f32 + uw / 2.0,
+                y: unit.y * h as f32 + uh / 2.0,
+            })
+        })
+        .collect()
 }
 
-pub fn unit_color(fuzzy_match_percent: f32) -> String {
-    let red = rgb(42, 49, 64);
-    let green = rgb(0, 200, 0);
-    let (r, g, b) = red.mix(green, fuzzy_match_percent / 100.0).into_components();
-    format!("#{:02x}{:02x}{:02x}", (r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8)
+/// Picks black or white text, whichever contrasts better with the `#rrggbb` fill.
+fn label_color(fill: &str) -> &'static str {
+    let value = u32::from_str_radix(fill.trim_start_matches('#'), 16).unwrap_or(0);
+    let (r, g, b) = ((value >> 16) & 0xff, (value >> 8) & 0xff, value & 0xff);
+    // Perceived brightness (ITU-R BT.601)
+    if r * 299 + g * 587 + b * 114 > 128 * 1000 {
+        "#000000"
+    } else {
+        "#ffffff"
+    }
+}
+
+fn rgb((r, g, b): (u8, u8, u8)) -> Srgb {
+    Srgb::new(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0)
 }
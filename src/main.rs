@@ -1,3 +1,4 @@
+mod blocking;
 mod config;
 mod cron;
 mod db;
@@ -7,33 +8,34 @@ mod models;
 mod svg;
 mod templates;
 mod util;
+mod verify;
 
 use std::{
     fs::File,
     io::BufReader,
     net::{Ipv4Addr, SocketAddr},
     sync::Arc,
-    time::Duration,
 };
 
 use axum::{http::header, Router};
 use tokio::{net::TcpListener, signal};
 use tower::ServiceBuilder;
 use tower_http::{
-    timeout::TimeoutLayer,
     trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer},
     ServiceBuilderExt,
 };
 use tracing_subscriber::{filter::LevelFilter, EnvFilter};
 
 use crate::{
-    config::Config, db::Database, github::GitHub, handlers::build_router, templates::Templates,
+    blocking::BlockingPool, config::Config, db::Database, github::GitHub, handlers::build_router,
+    templates::Templates,
 };
 
 #[derive(Clone)]
 struct AppState {
     config: Config,
     db: Database,
+    blocking: BlockingPool,
     github: GitHub,
     templates: Templates,
 }
@@ -53,10 +55,57 @@ async fn main() {
         let file = BufReader::new(File::open("config.yml").expect("Failed to open config file"));
         serde_yaml::from_reader(file).expect("Failed to parse config file")
     };
-    let db = Database::new(&config.app).await.expect("Failed to open database");
+    let blocking = BlockingPool::new(&config.limits);
+    let mut db =
+        Database::new(&config.app, blocking.clone()).await.expect("Failed to open database");
+
+    // Subcommands
+    let mut args = std::env::args().skip(1);
+    if let Some(command) = args.next() {
+        match command.as_str() {
+            "verify" => {
+                let mode = verify::VerifyMode::from_args(args).expect("Invalid arguments");
+                let github = if mode == verify::VerifyMode::Repair {
+                    Some(GitHub::new(&config.app).await.expect("Failed to create GitHub client"))
+                } else {
+                    None
+                };
+                let clean = verify::run(&mut db, github.as_ref(), mode)
+                    .await
+                    .expect("Failed to verify database");
+                db.close().await;
+                std::process::exit(if clean { 0 } else { 1 });
+            }
+            _ => panic!("Unknown command: {}", command),
+        }
+    }
+
+    // Backfill derived data after subcommands, so `verify` can still run on a broken database
+    db.fixup_report_measures().await.expect("Failed to backfill report measures");
+
     let github = GitHub::new(&config.app).await.expect("Failed to create GitHub client");
-    let templates = templates::create("templates");
-    let state = AppState { config, db: db.clone(), github, templates };
+
+    // Warm the report cache in the background
+    tokio::spawn({
+        let db = db.clone();
+        async move {
+            if let Err(e) = db.warm_report_cache().await {
+                tracing::error!("Failed to warm report cache: {:?}", e);
+            }
+        }
+    });
+
+    // Index any reports missing from search in the background
+    tokio::spawn({
+        let db = db.clone();
+        async move {
+            if let Err(e) = db.fixup_search_index().await {
+                tracing::error!("Failed to backfill search index: {:?}", e);
+            }
+        }
+    });
+    let templates = templates::create(templates::TEMPLATE_DIR);
+    let state = AppState { config, db: db.clone(), blocking, github, templates };
 
     // Refresh before starting the server
     // cron::refresh_projects(&mut state).await.expect("Failed to refresh projects");
@@ -90,9 +139,8 @@ fn app(state: AppState) -> Router {
                 .make_span_with(DefaultMakeSpan::new().level(tracing::Level::INFO))
                 .on_response(DefaultOnResponse::new().level(tracing::Level::INFO)),
         )
-        .layer(TimeoutLayer::new(Duration::from_secs(10)))
         .compression();
-    build_router().layer(middleware).with_state(state)
+    build_router(&state.config.limits).layer(middleware).with_state(state)
 }
 
 async fn shutdown_signal() {
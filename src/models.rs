@@ -1,7 +1,7 @@
 use std::{borrow::Cow, sync::Arc};
 
 use chrono::{DateTime, Utc};
-use objdiff_core::bindings::report::Report;
+use objdiff_core::bindings::report::{Measures, Report, ReportUnit};
 use serde::Serialize;
 
 #[derive(Debug, Clone, Eq, PartialEq, Serialize)]
@@ -16,7 +16,7 @@ pub struct Project {
 }
 
 impl Project {
-    pub fn name(&self) -> Cow<str> {
+    pub fn name(&self) -> Cow<'_, str> {
         if let Some(name) = self.name.as_ref() {
             Cow::Borrowed(name)
         } else {
@@ -29,6 +29,11 @@ impl Project {
     }
 
     pub fn repo_url(&self) -> String { format!("https://github.com/{}/{}", self.owner, self.repo) }
+
+    /// URL of a source file in the repository at the given commit.
+    pub fn source_url(&self, sha: &str, path: &str) -> String {
+        format!("{}/blob/{}/{}", self.repo_url(), sha, path)
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Serialize)]
@@ -52,6 +57,13 @@ impl ProjectInfo {
     }
 }
 
+/// Project info with the overall measures of the default version's latest report.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProjectSummary {
+    pub info: ProjectInfo,
+    pub measures: Measures,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Serialize)]
 pub struct Commit {
     pub sha: String,
@@ -80,3 +92,35 @@ pub struct ReportFile {
     pub version: String,
     pub report: Arc<Report>,
 }
+
+/// A unit as it appeared in a single report, or `None` if it was removed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UnitHistoryEntry {
+    pub commit: Commit,
+    pub unit: Option<Arc<ReportUnit>>,
+}
+
+/// A version's report at a commit, listing its units without loading them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VersionUnits {
+    pub version: String,
+    /// Overall or category measures, if the report has them
+    pub measures: Option<Measures>,
+    /// Name and ID of each unit. Units with the same ID are identical.
+    pub units: Vec<(String, Vec<u8>)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchResult {
+    pub owner: String,
+    pub repo: String,
+    pub project_name: Option<String>,
+    pub version: String,
+    pub commit: String,
+    /// Either "unit" or "function"
+    pub kind: String,
+    pub unit_name: String,
+    pub name: String,
+    pub demangled_name: Option<String>,
+    pub fuzzy_match_percent: Option<f32>,
+}
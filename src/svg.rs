@@ -1,22 +1,52 @@
 use std::{
     io::Cursor,
+    iter,
     sync::{Arc, OnceLock},
 };
 
 use anyhow::{anyhow, Context, Result};
 use image::{
-    buffer::ConvertBuffer, error::UnsupportedErrorKind, ExtendedColorType, ImageError, ImageFormat,
-    RgbImage, RgbaImage,
+    buffer::ConvertBuffer,
+    codecs::gif::{GifEncoder, Repeat},
+    error::UnsupportedErrorKind,
+    Delay, ExtendedColorType, Frame, ImageError, ImageFormat, RgbImage, RgbaImage,
 };
 use resvg::{
     tiny_skia::{PixmapMut, Transform},
     usvg::{fontdb, Options, Tree},
 };
-use tracing::info;
+use tracing::{debug, info};
 
 static FONT_DB: OnceLock<Arc<fontdb::Database>> = OnceLock::new();
 
+/// Minimum requested image width or height, in pixels.
+pub const MIN_IMAGE_SIZE: u32 = 16;
+/// Maximum rasterized image width or height, in pixels.
+pub const MAX_IMAGE_SIZE: u32 = 2048;
+
+/// Whether a requested image width or height is within the size range the rasterizer supports.
+pub fn is_valid_size(size: u32) -> bool { (MIN_IMAGE_SIZE..=MAX_IMAGE_SIZE).contains(&size) }
+
+/// Rasterizes an SVG. SVGs larger than [`MAX_IMAGE_SIZE`] in either dimension are scaled down
+/// to fit, so the effective size is that of the returned image rather than the SVG's.
 pub fn render_image(svg: &str, format: ImageFormat) -> Result<Vec<u8>> {
+    let image = render_pixmap(svg)?;
+    let mut bytes = Vec::new();
+    match image.write_to(&mut Cursor::new(&mut bytes), format) {
+        Ok(()) => {}
+        Err(ImageError::Unsupported(e))
+            if matches!(e.kind(), UnsupportedErrorKind::Color(ExtendedColorType::Rgba8)) =>
+        {
+            // Convert to RGB and try again
+            let image: RgbImage = image.convert();
+            image.write_to(&mut Cursor::new(&mut bytes), format)?;
+        }
+        Err(e) => return Err(e.into()),
+    }
+    Ok(bytes)
+}
+
+fn render_pixmap(svg: &str) -> Result<RgbaImage> {
     let fontdb = FONT_DB
         .get_or_init(|| {
             let mut db = fontdb::Database::new();
@@ -27,24 +57,145 @@ pub fn render_image(svg: &str, format: ImageFormat) -> Result<Vec<u8>> {
         .clone();
     let opt = Options { fontdb, ..Default::default() };
     let tree = Tree::from_str(svg, &opt).context("Failed to parse SVG")?;
-    let rect = tree.size().to_int_size();
-    let w = rect.width().clamp(1, 2048);
-    let h = rect.height().clamp(1, 2048);
+    // Requested sizes are validated up front, but computed ones (like long shield labels) may
+    // still be too large. Scale those down to fit rather than cropping them.
+    let size = tree.size();
+    let scale = (MAX_IMAGE_SIZE as f32 / size.width().max(size.height())).min(1.0);
+    let rect = size.to_int_size().scale_by(scale).ok_or_else(|| anyhow!("Invalid image size"))?;
+    let (w, h) = (rect.width(), rect.height());
+    if scale < 1.0 {
+        debug!("Scaled {}x{} SVG down to {}x{}", size.width(), size.height(), w, h);
+    }
     let mut image = RgbaImage::new(w, h);
     let mut pixmap = PixmapMut::from_bytes(image.as_mut(), w, h)
         .ok_or_else(|| anyhow!("Failed to create pixmap"))?;
-    resvg::render(&tree, Transform::identity(), &mut pixmap);
+    resvg::render(&tree, Transform::from_scale(scale, scale), &mut pixmap);
+    Ok(image)
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AnimationFormat {
+    Gif,
+    Apng,
+    WebP,
+}
+
+impl AnimationFormat {
+    pub fn from_mime_type(mime: &str) -> Option<Self> {
+        match mime {
+            "image/gif" => Some(Self::Gif),
+            "image/png" | "image/apng" => Some(Self::Apng),
+            "image/webp" => Some(Self::WebP),
+            _ => None,
+        }
+    }
+
+    pub fn to_mime_type(self) -> &'static str {
+        match self {
+            Self::Gif => "image/gif",
+            // Served as PNG, which is what browsers sniff APNG as
+            Self::Apng => "image/png",
+            Self::WebP => "image/webp",
+        }
+    }
+}
+
+/// Renders each SVG as a frame of an endlessly looping animation. SVGs are taken from the
+/// iterator and rasterized one at a time as they're encoded, so they can be produced while
+/// earlier frames are encoded. There must be exactly `frame_count` frames of the same size.
+pub fn render_animation(
+    mut svgs: impl Iterator<Item = String>,
+    frame_count: usize,
+    delay_ms: u32,
+    format: AnimationFormat,
+) -> Result<Vec<u8>> {
+    let Some(first) = svgs.next() else {
+        return Err(anyhow!("No frames to render"));
+    };
+    let first = render_pixmap(&first)?;
+    let (w, h) = first.dimensions();
+    let mut frames = iter::once(Ok(first)).chain(svgs.map(|svg| {
+        let image = render_pixmap(&svg)?;
+        if image.dimensions() != (w, h) {
+            return Err(anyhow!("Animation frames differ in size"));
+        }
+        Ok(image)
+    }));
     let mut bytes = Vec::new();
-    match image.write_to(&mut Cursor::new(&mut bytes), format) {
-        Ok(()) => {}
-        Err(ImageError::Unsupported(e))
-            if matches!(e.kind(), UnsupportedErrorKind::Color(ExtendedColorType::Rgba8)) =>
-        {
-            // Convert to RGB and try again
-            let image: RgbImage = image.convert();
-            image.write_to(&mut Cursor::new(&mut bytes), format)?;
+    match format {
+        AnimationFormat::Gif => {
+            let mut encoder = GifEncoder::new_with_speed(&mut bytes, 10);
+            encoder.set_repeat(Repeat::Infinite)?;
+            for image in frames {
+                let delay = Delay::from_numer_denom_ms(delay_ms, 1);
+                encoder.encode_frame(Frame::from_parts(image?, 0, 0, delay))?;
+            }
         }
-        Err(e) => return Err(e.into()),
+        AnimationFormat::Apng => {
+            let mut encoder = png::Encoder::new(&mut bytes, w, h);
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder.set_animated(frame_count as u32, 0)?;
+            encoder.set_frame_delay(delay_ms.min(u16::MAX as u32) as u16, 1000)?;
+            let mut writer = encoder.write_header()?;
+            for image in frames {
+                writer.write_image_data(image?.as_raw())?;
+            }
+            writer.finish()?;
+        }
+        AnimationFormat::WebP => write_animated_webp(&mut bytes, &mut frames, w, h, delay_ms)?,
     }
     Ok(bytes)
 }
+
+/// The WebP encoder only produces still images, so each frame is encoded separately and its
+/// VP8L bitstream is wrapped in an animation frame chunk.
+fn write_animated_webp(
+    out: &mut Vec<u8>,
+    frames: &mut dyn Iterator<Item = Result<RgbaImage>>,
+    w: u32,
+    h: u32,
+    delay_ms: u32,
+) -> Result<()> {
+    fn chunk(out: &mut Vec<u8>, name: &[u8; 4], data: &[u8]) {
+        out.extend_from_slice(name);
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            out.push(0);
+        }
+    }
+    fn u24(out: &mut Vec<u8>, value: u32) { out.extend_from_slice(&value.to_le_bytes()[..3]); }
+
+    let mut body = b"WEBP".to_vec();
+    let mut vp8x = vec![0b0001_0010, 0, 0, 0]; // Alpha and animation flags
+    u24(&mut vp8x, w - 1);
+    u24(&mut vp8x, h - 1);
+    chunk(&mut body, b"VP8X", &vp8x);
+    // Transparent background, loop forever
+    chunk(&mut body, b"ANIM", &[0, 0, 0, 0, 0, 0]);
+    for image in frames {
+        let mut still = Vec::new();
+        let image = image?;
+        image.write_to(&mut Cursor::new(&mut still), ImageFormat::WebP)?;
+        // Simple format: RIFF header, then a single VP8L chunk
+        if still.get(12..16) != Some(&b"VP8L"[..]) {
+            return Err(anyhow!("Unexpected WebP encoder output"));
+        }
+        let len = u32::from_le_bytes(still[16..20].try_into()?) as usize;
+        let bitstream = still.get(20..20 + len).context("Truncated WebP encoder output")?;
+        let mut anmf = Vec::with_capacity(bitstream.len() + 24);
+        u24(&mut anmf, 0); // X offset
+        u24(&mut anmf, 0); // Y offset
+        u24(&mut anmf, w - 1);
+        u24(&mut anmf, h - 1);
+        u24(&mut anmf, delay_ms.min(0xFFFFFF));
+        anmf.push(0b10); // Don't blend with the previous frame
+        chunk(&mut anmf, b"VP8L", bitstream);
+        chunk(&mut body, b"ANMF", &anmf);
+    }
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(&body);
+    Ok(())
+}
@@ -5,6 +5,11 @@ use chrono::{DateTime, Utc};
 use minijinja::{path_loader, Environment};
 use minijinja_autoreload::AutoReloader;
 
+use crate::util::format_bytes;
+
+/// Directory the templates are loaded from, relative to the working directory.
+pub const TEMPLATE_DIR: &str = "templates";
+
 pub type Templates = Arc<AutoReloader>;
 
 pub fn create(template_path: impl Into<String>) -> Templates {
@@ -18,6 +23,7 @@ pub fn create(template_path: impl Into<String>) -> Templates {
         env.set_lstrip_blocks(true);
         env.add_filter("date", date);
         env.add_filter("timeago", timeago);
+        env.add_filter("bytes", bytes);
         Ok(env)
     }))
 }
@@ -40,3 +46,12 @@ fn date(value: String, format: Option<String>) -> String {
     let format = format.as_deref().unwrap_or("%Y-%m-%d %H:%M:%S %Z");
     value.format(format).to_string()
 }
+
+fn bytes(value: i64) -> String {
+    let formatted = format_bytes(value.unsigned_abs());
+    if value < 0 {
+        format!("-{}", formatted)
+    } else {
+        formatted
+    }
+}
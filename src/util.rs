@@ -60,3 +60,18 @@ pub fn join_normalized(base: impl AsRef<Path>, path: impl AsRef<Path>) -> PathBu
     out.extend(path.as_ref().components().filter(|v| matches!(v, std::path::Component::Normal(_))));
     out
 }
+
+/// Formats a byte count with binary units, e.g. `1.5 KiB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 3] = ["KiB", "MiB", "GiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
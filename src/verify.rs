@@ -0,0 +1,110 @@
+use std::collections::BTreeSet;
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::{db::Database, github, github::GitHub};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum VerifyMode {
+    /// Only report problems.
+    Check,
+    /// Move broken reports into quarantine and delete corrupt or orphaned rows.
+    Quarantine,
+    /// Quarantine, then re-fetch broken reports from GitHub.
+    Repair,
+}
+
+impl VerifyMode {
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut mode = Self::Check;
+        for arg in args {
+            mode = match arg.as_str() {
+                "--quarantine" => Self::Quarantine,
+                "--repair" => Self::Repair,
+                _ => return Err(anyhow!("Unknown argument: {}", arg)),
+            };
+        }
+        Ok(mode)
+    }
+}
+
+/// Verifies the integrity of all stored reports. Returns `true` if no problems were found.
+pub async fn run(db: &mut Database, github: Option<&GitHub>, mode: VerifyMode) -> Result<bool> {
+    tracing::info!("Verifying database integrity");
+    let result = db.check_integrity().await.context("Failed to check database integrity")?;
+    for issue in &result.unit_issues {
+        tracing::warn!(
+            "Unit {} ({}): {}",
+            hex(&issue.id),
+            issue.name.as_deref().unwrap_or("unknown"),
+            issue.reason
+        );
+    }
+    for issue in &result.report_issues {
+        tracing::warn!(
+            "Report {} ({}/{} {} {}): {}",
+            issue.report_id,
+            issue.owner.as_deref().unwrap_or("?"),
+            issue.repo.as_deref().unwrap_or("?"),
+            issue.version,
+            issue.commit,
+            issue.problem
+        );
+    }
+    tracing::info!(
+        "Checked {} reports and {} units: {} broken reports, {} corrupt units, {} orphaned units, {} orphaned links",
+        result.reports_checked,
+        result.units_checked,
+        result.report_issues.len(),
+        result.unit_issues.len(),
+        result.orphaned_units,
+        result.orphaned_links
+    );
+    if result.is_clean() || mode == VerifyMode::Check {
+        return Ok(result.is_clean());
+    }
+
+    for issue in &result.report_issues {
+        db.quarantine_report(issue.report_id, &issue.problem.to_string())
+            .await
+            .with_context(|| format!("Failed to quarantine report {}", issue.report_id))?;
+    }
+    let corrupt_units = result.unit_issues.iter().map(|i| i.id.clone()).collect::<Vec<_>>();
+    db.delete_report_units(&corrupt_units).await.context("Failed to delete corrupt units")?;
+    let (units, links) = db.delete_orphans().await.context("Failed to delete orphaned rows")?;
+    tracing::info!(
+        "Quarantined {} reports, deleted {} corrupt units, {} orphaned units and {} orphaned links",
+        result.report_issues.len(),
+        corrupt_units.len(),
+        units,
+        links
+    );
+    if mode != VerifyMode::Repair {
+        return Ok(false);
+    }
+
+    let github = github.ok_or_else(|| anyhow!("GitHub client required for repair"))?;
+    // A workflow run contains every version, so only re-fetch each commit once
+    let commits = result
+        .report_issues
+        .iter()
+        .filter_map(|i| Some((i.owner.as_deref()?, i.repo.as_deref()?, i.commit.as_str())))
+        .collect::<BTreeSet<_>>();
+    for (owner, repo, commit) in commits {
+        let Some(project_info) = db.get_project_info(owner, repo, None).await? else {
+            continue;
+        };
+        match github::refetch_commit(github, db, &project_info.project, commit).await {
+            Ok(true) => {}
+            Ok(false) => {
+                tracing::warn!("No reports found for {}/{} commit {}", owner, repo, commit)
+            }
+            Err(e) => {
+                tracing::error!("Failed to re-fetch {}/{} commit {}: {:?}", owner, repo, commit, e)
+            }
+        }
+    }
+    Ok(false)
+}
+
+fn hex(data: &[u8]) -> String { data.iter().map(|b| format!("{:02x}", b)).collect() }